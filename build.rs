@@ -1,7 +1,11 @@
 #[cfg(feature = "network")]
 fn main() {
     let mut config = prost_build::Config::new();
-    config.type_attribute(".", "#[derive(PartialOrd)]");
+    // enum 已经由 prost 派生了 PartialOrd，只需要为 message 添加
+    config.message_attribute(".", "#[derive(PartialOrd)]");
+    // oneof 生成的 enum 是 message 的字段，也需要派生 PartialOrd
+    config.type_attribute(".abi.Request.body", "#[derive(PartialOrd)]");
+    config.type_attribute(".abi.Response.body", "#[derive(PartialOrd)]");
     config
         .out_dir("src/network/pb")
         .compile_protos(&["src/network/abi.proto"], &["src/network"])
@@ -1,13 +1,12 @@
 use anyhow::Result;
 use std::{collections::HashSet, env, iter, path::PathBuf};
-use tinympt::{self, ProofRequest, ProofResponse, RocksdbTrie, Trie, TrieError};
-use tokio::sync::oneshot;
+use tinympt::{
+    self, process_proof_request, Blake2bHasher, ProofRequest, ProofResponse, Request, RocksdbTrie,
+    Status, Trie, TrieError, Verified,
+};
 
 use clap::Parser;
-use futures::{
-    channel::mpsc::{self, UnboundedReceiver},
-    prelude::*,
-};
+use futures::prelude::*;
 use libp2p::{
     identity::{ed25519::SecretKey, Keypair},
     mdns,
@@ -36,18 +35,18 @@ struct Args {
     /// 构建 proof 请求时使用的根哈希
     #[arg(
         long,
-        default_value = "1b18217ad8a87e1accfdf7b3b1c4573985c932b711d6494db246e59fb884e952"
+        default_value = "ca732b9cccc46e2f87eaf1a80755b2c895149ceece6f3531b4e38c21872ffb07"
     )]
     root_hash: String,
     #[arg(long, default_value = "pellet02_state01_key02")]
-    /// 构建 proof 请求时使用的 key
+    /// 构建 proof 请求时使用的 key，以 0x 开头时按十六进制解析，用于二进制的 key
     key: String,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // 设置 log 级别
-    if let Err(_) = env::var("RUST_LOG") {
+    if env::var("RUST_LOG").is_err() {
         env::set_var("RUST_LOG", "info");
     }
 
@@ -56,21 +55,12 @@ async fn main() -> Result<()> {
     // 解析命令行参数
     let args = Args::parse();
 
-    // 定义一个 channle 用于接收请求
-    let (mut req_sender, req_receiver) =
-        mpsc::unbounded::<(ProofRequest, oneshot::Sender<ProofResponse>)>();
-
     // 构建一个 RocksdbTrie
-    let mut trie = RocksdbTrie::<String, String>::new(args.db_path);
+    let mut trie = RocksdbTrie::<Vec<u8>, String>::open(args.db_path)?;
     // 初始化 trie
     init_trie(&mut trie)?;
-
-    // 开启一个协程，处理 req_receiver 中的请求
-    tokio::spawn(async move {
-        if let Err(e) = process_proof_request(trie, req_receiver).await {
-            log::error!("Failed to process proof request; error = {}", e);
-        }
-    });
+    // 创建只读快照，处理请求时不需要 trie 的可变引用
+    let snapshot = trie.snapshot()?;
 
     // 生成 ed25519 公私钥对，如果提供了种子，就用种子生成
     let keypair = match args.secret_key_seed {
@@ -119,8 +109,10 @@ async fn main() -> Result<()> {
         .try_into()
         .map_err(|_| TrieError::InvalidHashValue)?;
 
-    // 从用户输入的数据转换成一个 proof_request
-    let proof_request = ProofRequest::from((root_hash, args.key.clone()));
+    // 用户输入的 key 可能是十六进制表示的二进制数据
+    let key = parse_key(&args.key)?;
+    // 从用户输入的数据转换成一个 proof_request，libp2p 的每个请求都有自己的 stream，不需要设置 request_id
+    let proof_request = Request::from(ProofRequest::from((root_hash, key.clone())));
 
     // 用来存储已经建立连接的 peer_id
     let mut peer_ids: HashSet<PeerId> = HashSet::new();
@@ -167,33 +159,32 @@ async fn main() -> Result<()> {
                 })) => match message {
                     // 3、当收到请求时，处理请求, 此时的节点是服务端身份
                     Message::Request { channel, request: proof_request, .. } => {
-                        // 定义一个 oneshot 通道，用来将处理结果
-                        let (res_sender, res_receiver) = oneshot::channel();
-                        // 将 proof_requset 连同 oneshot 一同发送
-                        req_sender.send((proof_request, res_sender)).await?;
-                        // 等待处理结果
-                        let proof_response = res_receiver.await?;
+                        // 处理请求，出错时也会返回一个带有错误状态的响应
+                        let response = process_proof_request(&snapshot, proof_request);
+                        if let Some(tinympt::response::Body::Proof(proof_response)) = &response.body {
+                            if proof_response.status() != Status::Ok {
+                                log::error!("Failed to process proof request; error = {}", proof_response.error);
+                            }
+                        }
                         // 将处理结果发送给客户端身份的节点
                         let _ =
                             swarm
                             .behaviour_mut()
                             .proof
-                            .send_response(channel, proof_response);
+                            .send_response(channel, response);
                     }
                     // 4、当收到响应时，处理响应，此时的节点是客户端身份
-                    Message::Response {
-                        response: proof_response,
-                        ..
-                    } => {
-                        log::info!("Proof response, exists = {}", proof_response.exists);
-                        // 将 proof_response 转换成 (bool, Vec<u8>)
-                        let (exists, proof_db) = proof_response.try_into()?;
-                        if exists {
-                            // 验证 proof, Some(value) 表示验证成功，None 表示验证失败
-                            let value: Option<String> =
-                                tinympt::verify_proof(&root_hash, &proof_db, &args.key)?;
-
-                            log::info!("Value = {:?}", value);
+                    Message::Response { response, .. } => {
+                        // 取出单个 key 的响应，响应的类型不对时返回错误
+                        let proof_response = ProofResponse::try_from(response)?;
+                        log::info!("Proof response, status = {:?}, exists = {}", proof_response.status(), proof_response.exists);
+                        // 将 proof_response 转换成 (bool, Proof)，RocksdbTrie 默认使用 Blake2bHasher
+                        // 服务端返回错误时，比如 root hash 不存在，这里会返回对应的错误
+                        let (_exists, proof) = proof_response.into_proof::<Blake2bHasher>()?;
+                        // 验证 proof, 服务端返回的 exists 不可信，以验证结果为准，proof 无效时返回错误
+                        match tinympt::verify_compact_proof::<Blake2bHasher, _, String>(&root_hash, &proof, &key)? {
+                            Verified::Present(value) => log::info!("Value = {:?}", value),
+                            Verified::ProvablyAbsent => log::info!("Key is provably absent"),
                         }
                     }
                 }
@@ -203,70 +194,45 @@ async fn main() -> Result<()> {
     }
 }
 
-/// 处理 req_receiver 中的请求
-async fn process_proof_request(
-    mut trie: RocksdbTrie<String, String>,
-    mut req_receiver: UnboundedReceiver<(ProofRequest, oneshot::Sender<ProofResponse>)>,
-) -> Result<()> {
-    // 从 req_receiver 中获取请求，然后处理请求，将结果发送回去。
-    // 注意，while 循环退出时，req_receiver 会被 drop, 导致服务端无法正常工作。
-    // 所以我们在 while 内部处理所有的错误，以免因为错误发生时导致 while 退出。
-    while let Some((proof_request, res_sender)) = req_receiver.next().await {
-        // 从 proof_request 中获取 hash_value 和 key
-        let (hash_value, key) = match proof_request.try_into() {
-            Ok((hash_value, key)) => (hash_value, key),
-            Err(e) => {
-                log::error!("Failed to convert proof request; error = {}", e);
-                continue;
-            }
-        };
-        // 从 trie 中获取 proof
-        let proof = match trie.get_proof(&hash_value, &key) {
-            Ok(proof) => proof,
-            Err(e) => {
-                log::error!("Failed to get proof; error = {}", e);
-                continue;
-            }
-        };
-        // 将 proof 转换为 proof_response
-        let proof_response = match proof.try_into() {
-            Ok(proof_response) => proof_response,
-            Err(e) => {
-                log::error!("Failed to convert proof; error = {}", e);
-                continue;
-            }
-        };
-        // 将 proof_response 发送回去
-        let _ = res_sender.send(proof_response);
+/// 解析用户输入的 key，以 0x 开头时按十六进制解析，否则使用字符串的字节
+fn parse_key(key: &str) -> Result<Vec<u8>> {
+    match key.strip_prefix("0x") {
+        Some(hex_key) => Ok(hex::decode(hex_key)?),
+        None => Ok(key.as_bytes().to_vec()),
     }
-    Ok(())
 }
 
 /// 为 trie 初始化数据
-fn init_trie(trie: &mut RocksdbTrie<String, String>) -> Result<()> {
+fn init_trie(trie: &mut RocksdbTrie<Vec<u8>, String>) -> Result<()> {
+    // 数据库里已经有提交过的数据，直接从最近一次提交的 root hash 继续
+    if let Some(root_hash) = trie.latest_root()? {
+        log::info!("Root hash = {:?}", hex::encode(root_hash));
+        return Ok(());
+    }
+
     let data = [
         (
-            "pellet01_state01_key01".to_string(),
+            b"pellet01_state01_key01".to_vec(),
             "pellet01_state01_value01".to_string(),
         ),
         (
-            "pellet01_state01_key02".to_string(),
+            b"pellet01_state01_key02".to_vec(),
             "pellet01_state01_value02".to_string(),
         ),
         (
-            "pellet01_state02_key01".to_string(),
+            b"pellet01_state02_key01".to_vec(),
             "pellet01_state02_value01".to_string(),
         ),
         (
-            "pellet01_state02_key02".to_string(),
+            b"pellet01_state02_key02".to_vec(),
             "pellet01_state02_value02".to_string(),
         ),
         (
-            "pellet02_state01_key01".to_string(),
+            b"pellet02_state01_key01".to_vec(),
             "pellet02_state01_value01".to_string(),
         ),
         (
-            "pellet02_state01_key02".to_string(),
+            b"pellet02_state01_key02".to_vec(),
             "pellet02_state01_value02".to_string(),
         ),
     ];
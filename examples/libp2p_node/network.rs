@@ -10,7 +10,7 @@ use libp2p::{
     swarm::NetworkBehaviour,
 };
 use prost::Message;
-use tinympt::{ProofRequest, ProofResponse};
+use tinympt::{Request, Response};
 use tokio_util::{
     codec::{FramedRead, FramedWrite, LengthDelimitedCodec},
     compat::{FuturesAsyncReadCompatExt, FuturesAsyncWriteCompatExt},
@@ -38,9 +38,9 @@ impl Codec for ProofCodec {
     /// 协议类型
     type Protocol = ProofProtocol;
     /// Request 类型
-    type Request = ProofRequest;
+    type Request = Request;
     /// Response 类型
-    type Response = ProofResponse;
+    type Response = Response;
 
     /// 从 io 里读取一个请求。
     /// 这里的 io 是多路复用后的 stream, 下同
@@ -54,9 +54,9 @@ impl Codec for ProofCodec {
         let mut reader = FramedRead::new(io.compat(), LengthDelimitedCodec::new());
         // 从 reader 里读取一个帧，如果读取成功，就返回帧的内容，否则返回 None
         if let Some(buf) = reader.try_next().await? {
-            // 从 buf 里解码出 ProofRequest
+            // 从 buf 里解码出 Request
             // decode 方法是 prost 里的，用于解码
-            ProofRequest::decode(buf).map_err(|_| Error::from(ErrorKind::UnexpectedEof))
+            Request::decode(buf).map_err(|_| Error::from(ErrorKind::UnexpectedEof))
         } else {
             Err(Error::from(ErrorKind::UnexpectedEof))
         }
@@ -73,7 +73,7 @@ impl Codec for ProofCodec {
     {
         let mut reader = FramedRead::new(io.compat(), LengthDelimitedCodec::new());
         if let Some(buf) = reader.try_next().await? {
-            ProofResponse::decode(buf).map_err(|_| Error::from(ErrorKind::UnexpectedEof))
+            Response::decode(buf).map_err(|_| Error::from(ErrorKind::UnexpectedEof))
         } else {
             Err(Error::from(ErrorKind::UnexpectedEof))
         }
@@ -84,7 +84,7 @@ impl Codec for ProofCodec {
         &mut self,
         _: &ProofProtocol,
         io: &mut T,
-        request: ProofRequest,
+        request: Request,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
@@ -102,7 +102,7 @@ impl Codec for ProofCodec {
         &mut self,
         _: &ProofProtocol,
         io: &mut T,
-        response: ProofResponse,
+        response: Response,
     ) -> io::Result<()>
     where
         T: AsyncWrite + Unpin + Send,
@@ -130,13 +130,13 @@ pub struct ComposedBehaviour {
 /// 组合的事件
 #[derive(Debug)]
 pub enum ComposedEvent {
-    Proof(request_response::Event<ProofRequest, ProofResponse>),
+    Proof(request_response::Event<Request, Response>),
     Mdns(mdns::Event),
 }
 
 /// 从 request_response::Event 转换为 ComposedEvent
-impl From<request_response::Event<ProofRequest, ProofResponse>> for ComposedEvent {
-    fn from(value: request_response::Event<ProofRequest, ProofResponse>) -> Self {
+impl From<request_response::Event<Request, Response>> for ComposedEvent {
+    fn from(value: request_response::Event<Request, Response>) -> Self {
         ComposedEvent::Proof(value)
     }
 }
@@ -0,0 +1,340 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::trie::TrieNode;
+use crate::{HashValue, Result};
+
+use super::Database;
+
+/// 缓存的容量
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheCapacity {
+    /// 最多缓存的条目数量
+    Entries(usize),
+    /// 最多缓存的字节数，只计算 value 的长度
+    Bytes(usize),
+}
+
+/// 缓存数据库，包装了另一个数据库，在内存里用 LRU 缓存最近读取的数据。
+///
+/// 每次从 trie 里查询数据都要从根节点开始读取路径上的节点，缓存之后，
+/// trie 上层经常被访问的节点就不需要每次都从数据库里读取了。
+///
+/// trie 通过 get_node 读取节点时，缓存里同时保存解码之后的节点，命中时不需要再次解码。
+///
+/// 写入和删除会同时更新被包装的数据库和缓存，丢弃批量写入时会清空缓存。
+#[derive(Debug)]
+pub struct CachingDatabase<D> {
+    db: D,
+    capacity: CacheCapacity,
+    // get 只有 &self, 需要内部可变性来更新缓存
+    cache: Mutex<LruCache>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<D> CachingDatabase<D> {
+    pub fn new(db: D, capacity: CacheCapacity) -> Self {
+        Self {
+            db,
+            capacity,
+            cache: Mutex::new(LruCache::default()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// 缓存命中的次数
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// 缓存未命中的次数
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// 缓存的条目数量
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+
+    /// 缓存是否为空
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 清空缓存，命中和未命中的次数保持不变
+    pub fn clear(&self) {
+        *self.cache.lock().unwrap() = LruCache::default();
+    }
+
+    /// 获得被包装的数据库
+    pub fn into_inner(self) -> D {
+        self.db
+    }
+
+    /// 将数据放入缓存，超出容量时淘汰最久没有使用的数据
+    fn put(&self, key: HashValue, value: Vec<u8>, node: Option<TrieNode>) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.insert(key, value, node);
+        loop {
+            let full = match self.capacity {
+                CacheCapacity::Entries(entries) => cache.entries.len() > entries,
+                CacheCapacity::Bytes(bytes) => cache.bytes > bytes,
+            };
+            if !full {
+                break;
+            }
+            cache.evict();
+        }
+    }
+}
+
+/// 实现 Database trait
+impl<D> Database for CachingDatabase<D>
+where
+    D: Database,
+{
+    fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+        if let Some(entry) = self.cache.lock().unwrap().get(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(entry.value.clone()));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let value = self.db.get(key)?;
+        if let Some(value) = value.as_ref() {
+            self.put(*key, value.clone(), None);
+        }
+        Ok(value)
+    }
+
+    fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Result<()> {
+        self.db.insert(key, value.clone())?;
+        self.put(key, value, None);
+        Ok(())
+    }
+
+    fn get_node(&self, key: &HashValue) -> Result<Option<TrieNode>> {
+        if let Some(entry) = self.cache.lock().unwrap().get(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            // 通过 get 或者 insert 放入缓存的数据，第一次作为节点读取时解码
+            if entry.node.is_none() {
+                entry.node = Some(TrieNode::decode(&entry.value)?);
+            }
+            return Ok(entry.node.clone());
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        match self.db.get(key)? {
+            Some(value) => {
+                let trie_node = TrieNode::decode(&value)?;
+                self.put(*key, value, Some(trie_node.clone()));
+                Ok(Some(trie_node))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn exists(&self, key: &HashValue) -> Result<bool> {
+        if self.cache.lock().unwrap().entries.contains_key(key) {
+            return Ok(true);
+        }
+        self.db.exists(key)
+    }
+
+    fn remove(&mut self, key: &HashValue) -> Result<()> {
+        self.db.remove(key)?;
+        self.cache.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<HashValue>> {
+        self.db.keys()
+    }
+
+    fn begin_batch(&mut self) -> Result<()> {
+        self.db.begin_batch()
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        self.db.write_batch()
+    }
+
+    fn discard_batch(&mut self) {
+        self.db.discard_batch();
+        // 缓存里可能有被丢弃的修改
+        self.clear();
+    }
+}
+
+/// 缓存的数据
+#[derive(Debug)]
+struct CacheEntry {
+    value: Vec<u8>,
+    // 解码之后的节点，数据作为节点读取过以后才有
+    node: Option<TrieNode>,
+    // 访问序号
+    tick: u64,
+}
+
+/// LRU 缓存，每次访问都会给数据分配一个递增的序号，序号最小的数据就是最久没有使用的数据
+#[derive(Debug, Default)]
+struct LruCache {
+    // key 到缓存的数据的映射
+    entries: HashMap<HashValue, CacheEntry>,
+    // 访问序号到 key 的映射，按序号排列
+    order: BTreeMap<u64, HashValue>,
+    // 下一个访问序号
+    tick: u64,
+    // 缓存的 value 的总字节数
+    bytes: usize,
+}
+
+impl LruCache {
+    /// 读取数据，并把它标记为最近使用
+    fn get(&mut self, key: &HashValue) -> Option<&mut CacheEntry> {
+        let tick = self.next_tick();
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        self.order.insert(tick, *key);
+        entry.tick = tick;
+        Some(entry)
+    }
+
+    /// 放入数据，并把它标记为最近使用
+    fn insert(&mut self, key: HashValue, value: Vec<u8>, node: Option<TrieNode>) {
+        self.remove(&key);
+        let tick = self.next_tick();
+        self.bytes += value.len();
+        self.entries.insert(key, CacheEntry { value, node, tick });
+        self.order.insert(tick, key);
+    }
+
+    /// 删除数据
+    fn remove(&mut self, key: &HashValue) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.bytes -= entry.value.len();
+            self.order.remove(&entry.tick);
+        }
+    }
+
+    /// 淘汰最久没有使用的数据
+    fn evict(&mut self) {
+        if let Some((_, key)) = self.order.pop_first() {
+            if let Some(entry) = self.entries.remove(&key) {
+                self.bytes -= entry.value.len();
+            }
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryDatabase;
+    use crate::{verify_proof, Blake2bHasher, MemoryTrie, Trie};
+
+    #[test]
+    fn caching_database_works() {
+        let mut db = CachingDatabase::new(MemoryDatabase::new(), CacheCapacity::Entries(2));
+        db.insert([1; 32], vec![1]).unwrap();
+        db.insert([2; 32], vec![2]).unwrap();
+        assert_eq!(db.len(), 2);
+
+        // 写入的数据已经在缓存里
+        assert_eq!(db.get(&[1; 32]).unwrap(), Some(vec![1]));
+        assert_eq!((db.hits(), db.misses()), (1, 0));
+
+        // 超出容量，淘汰最久没有使用的 [2; 32]
+        db.insert([3; 32], vec![3]).unwrap();
+        assert_eq!(db.len(), 2);
+        assert_eq!(db.get(&[2; 32]).unwrap(), Some(vec![2]));
+        assert_eq!((db.hits(), db.misses()), (1, 1));
+        // 再次读取时命中
+        assert_eq!(db.get(&[2; 32]).unwrap(), Some(vec![2]));
+        assert_eq!((db.hits(), db.misses()), (2, 1));
+
+        // 不存在的 key 不会被缓存
+        assert!(db.get(&[4; 32]).unwrap().is_none());
+        assert_eq!((db.hits(), db.misses()), (2, 2));
+
+        // 删除的数据也从缓存里删除
+        db.remove(&[2; 32]).unwrap();
+        assert!(db.get(&[2; 32]).unwrap().is_none());
+    }
+
+    #[test]
+    fn caching_database_bytes_capacity_works() {
+        let mut db = CachingDatabase::new(MemoryDatabase::new(), CacheCapacity::Bytes(10));
+        db.insert([1; 32], vec![0; 4]).unwrap();
+        db.insert([2; 32], vec![0; 4]).unwrap();
+        assert_eq!(db.len(), 2);
+        // 超出字节数，淘汰最久没有使用的数据
+        db.insert([3; 32], vec![0; 4]).unwrap();
+        assert_eq!(db.len(), 2);
+        // 比容量还大的数据不会留在缓存里
+        db.insert([4; 32], vec![0; 11]).unwrap();
+        assert!(db.is_empty());
+        // 数据仍然在被包装的数据库里
+        assert_eq!(db.into_inner().get(&[4; 32]).unwrap(), Some(vec![0; 11]));
+    }
+
+    #[test]
+    fn caching_database_trie_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        trie.insert("pellet01_state01_key01", "value01".to_string())
+            .unwrap();
+        trie.insert("pellet01_state01_key02", "value02".to_string())
+            .unwrap();
+        let root_hash = trie.commit().unwrap().unwrap();
+        let key = "pellet01_state01_key01";
+        let (_, proof_db) = trie.get_proof(&root_hash, &key).unwrap();
+        let db = CachingDatabase::new(proof_db, CacheCapacity::Entries(16));
+
+        // 第一次验证时从数据库里读取路径上的节点
+        verify_proof::<Blake2bHasher, _, String>(&root_hash, &db, &key).unwrap();
+        let misses = db.misses();
+        assert!(misses > 0);
+        assert_eq!(db.hits(), 0);
+
+        // 第二次验证时路径上的节点都在缓存里
+        verify_proof::<Blake2bHasher, _, String>(&root_hash, &db, &key).unwrap();
+        assert_eq!(db.misses(), misses);
+        assert_eq!(db.hits(), misses);
+    }
+
+    #[test]
+    fn caching_database_memory_trie_works() {
+        let db = CachingDatabase::new(MemoryDatabase::new(), CacheCapacity::Entries(64));
+        let mut trie = MemoryTrie::<&'static str, String, Blake2bHasher, _>::with_database(db);
+        trie.insert("pellet01_state01_key01", "value01".to_string())
+            .unwrap();
+        trie.insert("pellet01_state01_key02", "value02".to_string())
+            .unwrap();
+        trie.insert("pellet02_state01_key01", "value03".to_string())
+            .unwrap();
+        trie.commit().unwrap();
+        trie.db_ref().clear();
+        let (hits, misses) = (trie.db_ref().hits(), trie.db_ref().misses());
+
+        // 第一次查询时从被包装的数据库里读取路径上的节点
+        let key = "pellet01_state01_key01";
+        assert_eq!(trie.get_value(&key).unwrap().unwrap(), "value01");
+        let path_len = trie.db_ref().misses() - misses;
+        assert!(path_len > 0);
+        assert_eq!(trie.db_ref().hits(), hits);
+
+        // 之后的查询都命中缓存里解码之后的节点
+        for _ in 0..3 {
+            assert_eq!(trie.get_value(&key).unwrap().unwrap(), "value01");
+        }
+        assert_eq!(trie.db_ref().misses() - misses, path_len);
+        assert_eq!(trie.db_ref().hits() - hits, path_len * 3);
+    }
+}
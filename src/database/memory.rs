@@ -7,15 +7,22 @@ use crate::{HashValue, Result};
 use super::Database;
 
 /// 内存数据库
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// 克隆会复制所有的数据，开销是 O(n)，克隆之后两个数据库互不影响
+/// 批量写入的修改先缓存起来，write_batch 时一起写入，丢弃时数据库没有变化
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct MemoryDatabase {
     data: HashMap<HashValue, Vec<u8>>,
+    // 批量写入时缓存的修改，None 表示删除，读取时优先从这里读取
+    #[serde(skip)]
+    batch: Option<HashMap<HashValue, Option<Vec<u8>>>>,
 }
 
 impl MemoryDatabase {
     pub fn new() -> Self {
         Self {
             data: HashMap::new(),
+            batch: None,
         }
     }
 }
@@ -23,15 +30,86 @@ impl MemoryDatabase {
 /// 实现 Database trait
 impl Database for MemoryDatabase {
     fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+        if let Some(value) = self.batch.as_ref().and_then(|batch| batch.get(key)) {
+            return Ok(value.clone());
+        }
         Ok(self.data.get(key).cloned())
     }
 
     fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Result<()> {
-        self.data.insert(key, value);
+        match self.batch.as_mut() {
+            Some(batch) => {
+                batch.insert(key, Some(value));
+            }
+            None => {
+                self.data.insert(key, value);
+            }
+        }
         Ok(())
     }
 
     fn exists(&self, key: &HashValue) -> Result<bool> {
+        if let Some(value) = self.batch.as_ref().and_then(|batch| batch.get(key)) {
+            return Ok(value.is_some());
+        }
         Ok(self.data.contains_key(key))
     }
+
+    fn remove(&mut self, key: &HashValue) -> Result<()> {
+        match self.batch.as_mut() {
+            Some(batch) => {
+                batch.insert(*key, None);
+            }
+            None => {
+                self.data.remove(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<HashValue>> {
+        let mut keys: Vec<_> = self.data.keys().copied().collect();
+        // 合并批量写入里缓存的修改
+        if let Some(batch) = self.batch.as_ref() {
+            keys.retain(|key| !batch.contains_key(key));
+            keys.extend(
+                batch
+                    .iter()
+                    .filter(|(_, value)| value.is_some())
+                    .map(|(key, _)| *key),
+            );
+        }
+        Ok(keys)
+    }
+
+    fn begin_batch(&mut self) -> Result<()> {
+        self.batch = Some(HashMap::new());
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        if let Some(batch) = self.batch.take() {
+            for (key, value) in batch {
+                match value {
+                    Some(value) => self.data.insert(key, value),
+                    None => self.data.remove(&key),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    fn discard_batch(&mut self) {
+        self.batch = None;
+    }
+}
+
+/// 按任意顺序遍历数据库里所有的 key-value，不包括批量写入里还没有写入的修改
+impl<'a> IntoIterator for &'a MemoryDatabase {
+    type Item = (&'a HashValue, &'a Vec<u8>);
+    type IntoIter = std::collections::hash_map::Iter<'a, HashValue, Vec<u8>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.data.iter()
+    }
 }
@@ -1,13 +1,39 @@
+mod caching;
 mod memory;
 #[cfg(feature = "rocksdb")]
 mod rocksdb;
+mod verifying;
 
 #[cfg(feature = "rocksdb")]
 pub use crate::database::rocksdb::RocksdbDatabase;
+pub use caching::{CacheCapacity, CachingDatabase};
 pub use memory::MemoryDatabase;
+pub use verifying::VerifyingDatabase;
 
+use crate::trie::TrieNode;
 use crate::{HashValue, Result};
 
+/// 保存最近一次提交的 root hash 的保留 key
+/// 节点都以自身的 hash 作为 key 保存，全零的 key 不会与节点冲突
+pub(crate) const LATEST_ROOT_KEY: HashValue = [0; 32];
+
+/// 保存版本号索引的保留 key，版本记录本身保存在由版本号计算出来的 key 下
+pub(crate) const VERSIONS_KEY: HashValue = {
+    let mut key = [0; 32];
+    key[31] = 1;
+    key
+};
+
+/// 保存被固定的 root hash 的保留 key
+pub(crate) const PINNED_ROOTS_KEY: HashValue = {
+    let mut key = [0; 32];
+    key[31] = 2;
+    key
+};
+
+/// 所有的保留 key，垃圾回收时不会被删除
+pub(crate) const RESERVED_KEYS: [HashValue; 3] = [LATEST_ROOT_KEY, VERSIONS_KEY, PINNED_ROOTS_KEY];
+
 /// Database trait
 pub trait Database {
     /// 从数据里获得指定 key 的值
@@ -18,4 +44,51 @@ pub trait Database {
 
     /// 检查数据库里是否存在指定的 key
     fn exists(&self, key: &HashValue) -> Result<bool>;
+
+    /// 从数据库里删除指定的 key
+    fn remove(&mut self, key: &HashValue) -> Result<()>;
+
+    /// 获得数据库里所有的 key
+    fn keys(&self) -> Result<Vec<HashValue>>;
+
+    /// 读取并解码 key 对应的 trie 节点，trie 读取节点时使用
+    /// 默认实现每次都从 get 返回的数据解码，CachingDatabase 会缓存解码之后的节点
+    fn get_node(&self, key: &HashValue) -> Result<Option<TrieNode>> {
+        match self.get(key)? {
+            Some(bin_node) => Ok(Some(TrieNode::decode(&bin_node)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 开始批量写入，之后的 insert 和 remove 会被缓存起来，直到 write_batch 时一起写入
+    /// 默认实现直接写入，不支持原子写入的数据库不需要实现
+    fn begin_batch(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 原子地写入批量写入里缓存的所有修改
+    fn write_batch(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 丢弃批量写入里缓存的所有修改
+    fn discard_batch(&mut self) {}
+}
+
+/// 在一个批量写入里执行 f，f 成功时原子地写入所有修改，失败时丢弃所有修改
+pub(crate) fn write_in_batch<D, R>(db: &mut D, f: impl FnOnce(&mut D) -> Result<R>) -> Result<R>
+where
+    D: Database,
+{
+    db.begin_batch()?;
+    match f(db) {
+        Ok(result) => {
+            db.write_batch()?;
+            Ok(result)
+        }
+        Err(e) => {
+            db.discard_batch();
+            Err(e)
+        }
+    }
 }
@@ -1,32 +1,120 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
-use crate::{HashValue, Result};
-use rocksdb::DB;
+use crate::{HashValue, Result, TrieError};
+use rocksdb::{IteratorMode, WriteBatch, DB};
 
 use super::Database;
 
 #[derive(Debug)]
 pub struct RocksdbDatabase {
-    db: DB,
+    // rocksdb 本身是线程安全的，克隆的数据库共享同一个实例
+    db: Arc<DB>,
+    // 批量写入时缓存的修改，None 表示删除，读取时优先从这里读取
+    batch: Option<HashMap<HashValue, Option<Vec<u8>>>>,
 }
 
 impl RocksdbDatabase {
     pub fn new(db_path: PathBuf) -> Self {
         let db = DB::open_default(db_path).unwrap();
-        Self { db }
+        Self {
+            db: Arc::new(db),
+            batch: None,
+        }
+    }
+}
+
+/// 克隆的数据库与原来的数据库共享同一个 rocksdb 实例，一方写入的数据另一方也可以读取
+/// 批量写入里还没有写入的修改不会被克隆
+impl Clone for RocksdbDatabase {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            batch: None,
+        }
     }
 }
 
 impl Database for RocksdbDatabase {
     fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+        if let Some(value) = self.batch.as_ref().and_then(|batch| batch.get(key)) {
+            return Ok(value.clone());
+        }
         Ok(self.db.get(key)?)
     }
 
     fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Result<()> {
-        Ok(self.db.put(key, value)?)
+        match self.batch.as_mut() {
+            Some(batch) => {
+                batch.insert(key, Some(value));
+                Ok(())
+            }
+            None => Ok(self.db.put(key, value)?),
+        }
     }
 
     fn exists(&self, key: &HashValue) -> Result<bool> {
-        Ok(self.db.key_may_exist(key))
+        if let Some(value) = self.batch.as_ref().and_then(|batch| batch.get(key)) {
+            return Ok(value.is_some());
+        }
+        // 只查询布隆过滤器可能有假阳性，所以读取 key 确认是否存在，get_pinned 不会复制数据
+        Ok(self.db.get_pinned(key)?.is_some())
+    }
+
+    fn remove(&mut self, key: &HashValue) -> Result<()> {
+        match self.batch.as_mut() {
+            Some(batch) => {
+                batch.insert(*key, None);
+                Ok(())
+            }
+            None => Ok(self.db.delete(key)?),
+        }
+    }
+
+    fn keys(&self) -> Result<Vec<HashValue>> {
+        let mut keys = self
+            .db
+            .iterator(IteratorMode::Start)
+            .map(|item| {
+                let (key, _) = item?;
+                HashValue::try_from(key.as_ref()).map_err(|_| TrieError::InvalidHashValue)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // 合并批量写入里缓存的修改
+        if let Some(batch) = self.batch.as_ref() {
+            keys.retain(|key| !batch.contains_key(key));
+            keys.extend(
+                batch
+                    .iter()
+                    .filter(|(_, value)| value.is_some())
+                    .map(|(key, _)| *key),
+            );
+        }
+        Ok(keys)
+    }
+
+    fn begin_batch(&mut self) -> Result<()> {
+        self.batch = Some(HashMap::new());
+        Ok(())
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        // 使用 rocksdb 的 WriteBatch 原子地写入所有的修改
+        if let Some(batch) = self.batch.take() {
+            let mut write_batch = WriteBatch::default();
+            for (key, value) in batch {
+                match value {
+                    Some(value) => write_batch.put(key, value),
+                    None => write_batch.delete(key),
+                }
+            }
+            self.db.write(write_batch)?;
+        }
+        Ok(())
+    }
+
+    fn discard_batch(&mut self) {
+        self.batch = None;
     }
 }
@@ -0,0 +1,98 @@
+use std::marker::PhantomData;
+
+use crate::trie::TrieHasher;
+use crate::{HashValue, Result, TrieError};
+
+use super::Database;
+
+/// 校验数据库，包装了另一个数据库，读取节点和检查节点是否存在时都会使用 H 重新计算节点数据的 hash，
+/// 并与 key 对比，不一致时返回 TrieError::InvalidProof。
+/// 用于验证来自网络的 proof, 防止恶意的服务端在某个 hash 下放入其他的节点
+#[derive(Debug)]
+pub struct VerifyingDatabase<'a, D, H> {
+    db: &'a D,
+    // H 只在读取时使用, 使用 PhantomData 来避免编译器报错
+    _h: PhantomData<H>,
+}
+
+impl<'a, D, H> VerifyingDatabase<'a, D, H> {
+    pub fn new(db: &'a D) -> Self {
+        Self {
+            db,
+            _h: PhantomData,
+        }
+    }
+}
+
+/// 实现 Database trait
+impl<'a, D, H> Database for VerifyingDatabase<'a, D, H>
+where
+    D: Database,
+    H: TrieHasher,
+{
+    fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+        match self.db.get(key)? {
+            // 节点数据的 hash 必须与 key 一致
+            Some(value) if H::hash(&value) != *key => Err(TrieError::InvalidProof),
+            value => Ok(value),
+        }
+    }
+
+    fn insert(&mut self, _key: HashValue, _value: Vec<u8>) -> Result<()> {
+        // 校验数据库只用于读取 proof, 不允许写入
+        Err(TrieError::Database(
+            "VerifyingDatabase is read-only".to_string(),
+        ))
+    }
+
+    fn exists(&self, key: &HashValue) -> Result<bool> {
+        // 与 get 相同，数据与 key 不一致的节点不能算作存在
+        Ok(self.get(key)?.is_some())
+    }
+
+    fn remove(&mut self, _key: &HashValue) -> Result<()> {
+        Err(TrieError::Database(
+            "VerifyingDatabase is read-only".to_string(),
+        ))
+    }
+
+    fn keys(&self) -> Result<Vec<HashValue>> {
+        self.db.keys()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryDatabase;
+    use crate::trie::Blake2bHasher;
+
+    #[test]
+    fn verifying_database_works() {
+        let node = b"node".to_vec();
+        let hash_value = Blake2bHasher::hash(&node);
+        let mut db = MemoryDatabase::new();
+        db.insert(hash_value, node).unwrap();
+        // 数据与 key 不一致
+        db.insert([1; 32], b"tampered".to_vec()).unwrap();
+
+        let verifying_db = VerifyingDatabase::<_, Blake2bHasher>::new(&db);
+        assert_eq!(
+            verifying_db.get(&hash_value).unwrap(),
+            Some(b"node".to_vec())
+        );
+        assert!(verifying_db.exists(&hash_value).unwrap());
+        // 不存在的 key
+        assert!(verifying_db.get(&[2; 32]).unwrap().is_none());
+        assert!(!verifying_db.exists(&[2; 32]).unwrap());
+        // 被篡改的节点，get 和 exists 都返回错误
+        assert!(matches!(
+            verifying_db.get(&[1; 32]),
+            Err(TrieError::InvalidProof)
+        ));
+        assert!(matches!(
+            verifying_db.exists(&[1; 32]),
+            Err(TrieError::InvalidProof)
+        ));
+    }
+}
@@ -16,4 +16,24 @@ pub enum TrieError {
     InvalidHashValue,
     #[error("InvalidKey")]
     InvalidKey,
+    /// proof 无法证明结论：缺少路径上的节点，或者节点的数据与它的 hash 不一致（被篡改）
+    #[error("InvalidProof")]
+    InvalidProof,
+    #[error("InvalidVersion")]
+    InvalidVersion,
+    #[error("UnknownRoot")]
+    UnknownRoot,
+    /// 最近一次提交的 root hash 不能被取消固定，否则重新打开数据库时无法读取它
+    #[error("LatestRoot")]
+    LatestRoot,
+    #[error("InvalidNode")]
+    InvalidNode,
+    #[error("UnsupportedNodeVersion: {0}")]
+    UnsupportedNodeVersion(u8),
+    #[error("Timeout")]
+    Timeout,
+
+    #[cfg(feature = "network")]
+    #[error("Network error: {0}")]
+    Network(String),
 }
@@ -13,13 +13,28 @@ pub type NibbleSlice = [u8];
 /// 为 nibble vec 定义一个类型
 pub type NibbleVec = Vec<u8>;
 
+#[cfg(feature = "client")]
+pub use network::client::ProofClient;
+#[cfg(feature = "server")]
+pub use network::server::ProofServer;
 #[cfg(feature = "network")]
-pub use network::{ProofRequest, ProofResponse};
+pub use network::{
+    process_proof_request, request, response, ProofBatchRequest, ProofBatchResponse, ProofRequest,
+    ProofResponse, Request, Response, Status,
+};
 
 #[cfg(feature = "rocksdb")]
 pub use database::RocksdbDatabase;
-pub use database::{Database, MemoryDatabase};
+pub use database::{CacheCapacity, CachingDatabase, Database, MemoryDatabase, VerifyingDatabase};
 #[cfg(feature = "rocksdb")]
 pub use trie::rocksdb_trie::RocksdbTrie;
-pub use trie::verify_proof;
+#[cfg(feature = "keccak")]
+pub use trie::KeccakHasher;
+#[cfg(feature = "sha2")]
+pub use trie::Sha256Hasher;
+pub use trie::{
+    gc, verify_compact_proof, verify_multi_proof, verify_prefix_proof, verify_proof,
+    verify_range_proof, Blake2bHasher, GcStats, Proof, RangeProof, TrieHasher, TrieIterator,
+    TrieSnapshot, Verified, Version, NODE_CODEC_VERSION,
+};
 pub use trie::{memory_trie::MemoryTrie, Trie};
@@ -0,0 +1,614 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::stream::SplitSink;
+use futures::{SinkExt, StreamExt};
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio_util::time::{delay_queue, DelayQueue};
+
+use super::{
+    ProofBatchRequest, ProofBatchResponse, ProofRequest, ProofResponse, Request, Response,
+};
+use crate::trie::{verify_compact_proof, verify_multi_proof, Blake2bHasher, TrieHasher, Verified};
+use crate::{HashValue, Result, TrieError};
+
+/// 默认的请求超时时间
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// 最多排队等待发送的请求数量
+const MAX_QUEUED_REQUESTS: usize = 64;
+/// 最多已经发送、等待响应的请求数量，达到之后新的请求在队列里等待
+const MAX_PENDING_REQUESTS: usize = 1024;
+
+/// 等待响应的请求，连接出错时收到错误
+type PendingResponse = oneshot::Sender<Result<Response>>;
+
+/// 交给后台任务发送的请求
+#[derive(Debug)]
+struct QueuedRequest {
+    request: Request,
+    sender: PendingResponse,
+    // 请求超时的时间，之后后台任务不再等待它的响应
+    deadline: Instant,
+}
+
+/// 连接到 ProofServer 的 TCP 轻客户端，返回的数据都已经用 proof 验证过
+///
+/// 所有的请求共享同一个连接，由后台任务读写。多个请求可以同时发出而不用等待前一个响应，
+/// 后台任务给每个请求分配从 1 开始的 request_id，服务端可以乱序返回响应，后台任务按 request_id 把响应交给对应的请求。
+/// request_id 0 留给服务端无法解码请求时的错误响应，这样的响应不对应任何请求，会被忽略。
+/// 克隆的客户端共享同一个连接，所有的克隆都被 drop 之后连接关闭。
+///
+/// 服务端返回的响应不可信，客户端只公开验证之后的结果，proof 无效时返回 TrieError::InvalidProof。
+///
+/// H 是服务端的 trie 计算节点 hash 使用的 hash 函数，默认为 Blake2bHasher，
+/// 服务端使用其他 hash 函数时用 with_hasher 设置，比如 `ProofClient::connect(addr).await?.with_hasher::<KeccakHasher>()`
+#[derive(Debug)]
+pub struct ProofClient<H = Blake2bHasher> {
+    requests: mpsc::Sender<QueuedRequest>,
+    request_timeout: Duration,
+    // H 只在验证 proof 时使用, 使用 PhantomData 来避免编译器报错
+    _h: PhantomData<H>,
+}
+
+/// 克隆的客户端共享同一个连接，不要求 H 实现 Clone
+impl<H> Clone for ProofClient<H> {
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+            request_timeout: self.request_timeout,
+            _h: PhantomData,
+        }
+    }
+}
+
+impl ProofClient {
+    /// 连接到服务端
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await.map_err(network_error)?;
+        Ok(Self::new(stream))
+    }
+
+    /// 使用已经建立的连接创建客户端，可以自己控制连接的超时等参数
+    pub fn new(stream: TcpStream) -> Self {
+        let (requests, receiver) = mpsc::channel(MAX_QUEUED_REQUESTS);
+        let framed = Framed::new(stream, LengthDelimitedCodec::new());
+        tokio::spawn(process_stream(framed, receiver));
+        Self {
+            requests,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            _h: PhantomData,
+        }
+    }
+}
+
+impl<H: TrieHasher> ProofClient<H> {
+    /// 设置验证 proof 时使用的 hash 函数，必须与服务端的 trie 使用的 hash 函数相同
+    pub fn with_hasher<T: TrieHasher>(self) -> ProofClient<T> {
+        ProofClient {
+            requests: self.requests,
+            request_timeout: self.request_timeout,
+            _h: PhantomData,
+        }
+    }
+
+    /// 设置请求的超时时间，包括排队、发送和等待响应的时间
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// 获得 root hash 对应的 trie 里 key 的 value，key 不存在时返回 None
+    ///
+    /// 返回之前总是用服务端返回的 proof 验证，key 不存在时也需要 proof 证明。
+    /// 服务端返回错误时返回对应的 TrieError，比如 root hash 不存在时返回 TrieError::UnknownRoot。
+    pub async fn get_verified<V>(
+        &self,
+        root_hash: &HashValue,
+        key: impl AsRef<[u8]>,
+    ) -> Result<Option<V>>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        let proof_request = ProofRequest::from((*root_hash, key.as_ref()));
+        let response = self.request(proof_request.into()).await?;
+        // 服务端返回的 exists 不可信，以验证结果为准
+        let (_, proof) = ProofResponse::try_from(response)?.into_proof::<H>()?;
+        match verify_compact_proof::<H, _, V>(root_hash, &proof, &key.as_ref())? {
+            Verified::Present(value) => Ok(Some(value)),
+            Verified::ProvablyAbsent => Ok(None),
+        }
+    }
+
+    /// 在一个请求里获得 root hash 对应的 trie 里多个 key 的 value，按 keys 的顺序返回，key 不存在时为 None
+    ///
+    /// 服务端返回所有 key 共享的 proof，路径上的节点只传输一次，返回之前用 verify_multi_proof 验证。
+    /// 只要有一个 key 的路径不完整，就返回 TrieError::InvalidProof。
+    pub async fn get_verified_many<V>(
+        &self,
+        root_hash: &HashValue,
+        keys: &[impl AsRef<[u8]>],
+    ) -> Result<Vec<Option<V>>>
+    where
+        V: Serialize + DeserializeOwned,
+    {
+        let keys: Vec<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+        let batch_request = ProofBatchRequest::from((*root_hash, keys.clone()));
+        let response = self.request(batch_request.into()).await?;
+        let proof = ProofBatchResponse::try_from(response)?.into_proof::<H>()?;
+        let values = verify_multi_proof::<H, _, V>(root_hash, &proof, &keys)?
+            .into_iter()
+            .map(|verified| match verified {
+                Verified::Present(value) => Some(value),
+                Verified::ProvablyAbsent => None,
+            })
+            .collect();
+        Ok(values)
+    }
+
+    /// 发送请求并等待响应，超时返回 TrieError::Timeout
+    /// 响应还没有验证，所以不能公开
+    async fn request(&self, request: Request) -> Result<Response> {
+        let (sender, receiver) = oneshot::channel();
+        let deadline = Instant::now() + self.request_timeout;
+        let queued_request = QueuedRequest {
+            request,
+            sender,
+            deadline,
+        };
+        let response = async {
+            self.requests
+                .send(queued_request)
+                .await
+                .map_err(|_| connection_closed())?;
+            // 后台任务退出时 sender 被 drop
+            receiver.await.map_err(|_| connection_closed())?
+        };
+        tokio::time::timeout_at(deadline, response)
+            .await
+            .map_err(|_| TrieError::Timeout)?
+    }
+}
+
+/// 后台任务，给请求分配 request_id 并交给发送任务，按 request_id 把响应交给等待中的请求
+/// 请求在收到响应或者超时的时候被丢掉，超时的请求的响应到达时被忽略。
+/// 所有的客户端都被 drop 并且收到所有的响应之后退出，连接出错时所有等待中的请求都失败
+async fn process_stream(
+    framed: Framed<TcpStream, LengthDelimitedCodec>,
+    mut requests: mpsc::Receiver<QueuedRequest>,
+) {
+    // 发送和读取分开，发送阻塞的时候仍然可以读取响应，否则服务端也在等待客户端读取响应时会死锁
+    let (writer, mut reader) = framed.split();
+    let (outgoing, receiver) = mpsc::unbounded_channel();
+    let mut writing = tokio::spawn(write_requests(writer, receiver));
+    let mut pending: HashMap<u64, (PendingResponse, delay_queue::Key)> = HashMap::new();
+    // 等待中的请求的超时时间
+    let mut deadlines: DelayQueue<u64> = DelayQueue::new();
+    // request_id 0 留给服务端无法解码请求时的错误响应
+    let mut next_request_id = 1u64;
+    let mut closed = false;
+    let error = loop {
+        if closed && pending.is_empty() {
+            return;
+        }
+        // 等待中的请求太多时先不发送新的请求，所以发送任务的队列也不会无限增长
+        let accepting = !closed && pending.len() < MAX_PENDING_REQUESTS;
+        tokio::select! {
+            request = requests.recv(), if accepting => match request {
+                Some(QueuedRequest { mut request, sender, deadline }) => {
+                    let request_id = next_request_id;
+                    next_request_id = next_request_id.checked_add(1).unwrap_or(1);
+                    request.request_id = request_id;
+                    // 发送任务退出时，下面的分支会处理它的错误
+                    let _ = outgoing.send(Bytes::from(request.encode_to_vec()));
+                    let key = deadlines.insert_at(request_id, deadline);
+                    pending.insert(request_id, (sender, key));
+                }
+                // 所有的客户端都被 drop 了
+                None => closed = true,
+            },
+            result = &mut writing => break match result {
+                Ok(Ok(())) => "connection closed".to_string(),
+                Ok(Err(e)) => e.to_string(),
+                Err(e) => e.to_string(),
+            },
+            bytes = reader.next() => {
+                let bytes = match bytes {
+                    Some(Ok(bytes)) => bytes,
+                    Some(Err(e)) => break e.to_string(),
+                    None => break "connection closed".to_string(),
+                };
+                let response = match Response::decode(bytes) {
+                    Ok(response) => response,
+                    Err(e) => break e.to_string(),
+                };
+                // 服务端无法解码某个请求，不知道是哪个请求，这个请求会超时
+                if response.request_id == 0 {
+                    continue;
+                }
+                // 超时的请求已经被丢掉了，它的响应被忽略
+                if let Some((sender, key)) = pending.remove(&response.request_id) {
+                    deadlines.remove(&key);
+                    let _ = sender.send(Ok(response));
+                }
+            }
+            Some(expired) = deadlines.next(), if !deadlines.is_empty() => {
+                if let Some((sender, _)) = pending.remove(expired.get_ref()) {
+                    let _ = sender.send(Err(TrieError::Timeout));
+                }
+            }
+        }
+    };
+    writing.abort();
+    for (_, (sender, _)) in pending {
+        let _ = sender.send(Err(TrieError::Network(error.clone())));
+    }
+}
+
+/// 发送任务，依次发送 process_stream 交给它的请求，发送失败时退出
+async fn write_requests(
+    mut writer: SplitSink<Framed<TcpStream, LengthDelimitedCodec>, Bytes>,
+    mut outgoing: mpsc::UnboundedReceiver<Bytes>,
+) -> std::io::Result<()> {
+    while let Some(bytes) = outgoing.recv().await {
+        writer.send(bytes).await?;
+    }
+    Ok(())
+}
+
+fn network_error(e: std::io::Error) -> TrieError {
+    TrieError::Network(e.to_string())
+}
+
+fn connection_closed() -> TrieError {
+    TrieError::Network("connection closed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryDatabase;
+    use crate::network::tests::snapshot;
+    use crate::network::{invalid_request, process_proof_request, response};
+    use crate::{MemoryTrie, Proof, Trie, TrieSnapshot};
+    use tokio::net::{TcpListener, TcpSocket};
+
+    /// 使用 process_proof_request 生成请求的响应
+    fn respond(
+        snapshot: &TrieSnapshot<MemoryDatabase, Vec<u8>, String>,
+        bytes: bytes::BytesMut,
+    ) -> Response {
+        process_proof_request(snapshot, Request::decode(bytes).unwrap())
+    }
+
+    /// 启动一个按顺序处理请求的服务端，handler 可以篡改响应，返回服务端的地址
+    async fn start(
+        snapshot: TrieSnapshot<MemoryDatabase, Vec<u8>, String>,
+        handler: fn(Response) -> Response,
+    ) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        serve(listener, snapshot, handler)
+    }
+
+    /// 在 listener 上接收一个连接，发送完一个请求的响应之后才读取下一个请求
+    fn serve(
+        listener: TcpListener,
+        snapshot: TrieSnapshot<MemoryDatabase, Vec<u8>, String>,
+        handler: fn(Response) -> Response,
+    ) -> std::net::SocketAddr {
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            while let Some(Ok(bytes)) = framed.next().await {
+                let response = respond(&snapshot, bytes);
+                let bytes = Bytes::from(handler(response).encode_to_vec());
+                if framed.send(bytes).await.is_err() {
+                    break;
+                }
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn proof_client_works() {
+        let (snapshot, root_hash) = snapshot();
+        let addr = start(snapshot, |response| response).await;
+        let client = ProofClient::connect(addr).await.unwrap();
+
+        // 同一个连接上同时发出多个请求
+        let requests = (0..8u8).map(|i| {
+            let client = client.clone();
+            async move { client.get_verified::<String>(&root_hash, [0xff, i]).await }
+        });
+        let values = futures::future::join_all(requests).await;
+        for (i, value) in values.into_iter().enumerate() {
+            assert_eq!(value.unwrap(), Some(format!("value{i:02}")));
+        }
+
+        // 不存在的 key
+        let value = client.get_verified::<String>(&root_hash, [0xff, 9]).await;
+        assert_eq!(value.unwrap(), None);
+        // 不存在的 root hash
+        let result = client.get_verified::<String>(&[1; 32], [0xff]).await;
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+    }
+
+    #[tokio::test]
+    async fn proof_client_get_verified_many_works() {
+        let (snapshot, root_hash) = snapshot();
+        let addr = start(snapshot, |response| response).await;
+        let client = ProofClient::connect(addr).await.unwrap();
+
+        // 按 keys 的顺序返回，不存在的 key 为 None
+        let keys = [[0xff, 3], [0xff, 9], [0xff, 0]];
+        let values = client.get_verified_many::<String>(&root_hash, &keys).await;
+        assert_eq!(
+            values.unwrap(),
+            vec![
+                Some("value03".to_string()),
+                None,
+                Some("value00".to_string())
+            ]
+        );
+
+        // 批量请求和单个 key 的请求共享同一个连接
+        let (many, one) = tokio::join!(
+            client.get_verified_many::<String>(&root_hash, &[[0xff, 1], [0xff, 2]]),
+            client.get_verified::<String>(&root_hash, [0xff, 4]),
+        );
+        assert_eq!(
+            many.unwrap(),
+            vec![Some("value01".to_string()), Some("value02".to_string())]
+        );
+        assert_eq!(one.unwrap(), Some("value04".to_string()));
+
+        // 不存在的 root hash
+        let result = client
+            .get_verified_many::<String>(&[1; 32], &[[0xff]])
+            .await;
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+    }
+
+    #[tokio::test]
+    async fn proof_client_out_of_order_works() {
+        let (snapshot, root_hash) = snapshot();
+        // 每收到两个请求，先返回第二个请求的响应
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            while let (Some(Ok(first)), Some(Ok(second))) =
+                (framed.next().await, framed.next().await)
+            {
+                for bytes in [second, first] {
+                    let response = respond(&snapshot, bytes);
+                    let bytes = Bytes::from(response.encode_to_vec());
+                    framed.send(bytes).await.unwrap();
+                }
+            }
+        });
+
+        let client = ProofClient::connect(addr).await.unwrap();
+        let (first, second) = tokio::join!(
+            client.get_verified::<String>(&root_hash, [0xff, 1]),
+            client.get_verified::<String>(&root_hash, [0xff, 2]),
+        );
+        assert_eq!(first.unwrap(), Some("value01".to_string()));
+        assert_eq!(second.unwrap(), Some("value02".to_string()));
+    }
+
+    #[tokio::test]
+    async fn proof_client_request_id_works() {
+        let (snapshot, root_hash) = snapshot();
+        // 每个请求的响应之前，先返回一个 request_id 为 0 的错误响应，并把请求的 request_id 发给测试
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (request_ids, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            while let Some(Ok(bytes)) = framed.next().await {
+                let response = respond(&snapshot, bytes);
+                request_ids.send(response.request_id).unwrap();
+                let invalid = invalid_request(0, "invalid request".to_string());
+                for response in [invalid, response] {
+                    framed
+                        .send(Bytes::from(response.encode_to_vec()))
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+
+        // request_id 从 1 开始，request_id 为 0 的响应被忽略
+        let client = ProofClient::connect(addr).await.unwrap();
+        for i in 1..=3u8 {
+            let value = client.get_verified::<String>(&root_hash, [0xff, i]).await;
+            assert_eq!(value.unwrap(), Some(format!("value{i:02}")));
+            assert_eq!(receiver.recv().await.unwrap(), i as u64);
+        }
+    }
+
+    /// 发送和接收缓冲区都很小的 socket
+    fn small_buffer_socket() -> TcpSocket {
+        let socket = TcpSocket::new_v4().unwrap();
+        socket.set_send_buffer_size(4096).unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        socket
+    }
+
+    #[tokio::test]
+    async fn proof_client_large_requests_works() {
+        // value 很大的 key，响应比连接的缓冲区大很多
+        let mut trie = MemoryTrie::<Vec<u8>, String>::new();
+        let large_value = "a".repeat(256 << 10);
+        trie.insert(vec![0xff, 0], large_value.clone()).unwrap();
+        trie.insert(vec![0xff, 1], "value01".to_string()).unwrap();
+        let root_hash = trie.commit().unwrap().unwrap();
+        let socket = small_buffer_socket();
+        socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = serve(socket.listen(16).unwrap(), trie.snapshot().unwrap(), |r| r);
+
+        // 请求里的 key 很多，请求也比缓冲区大很多
+        let stream = small_buffer_socket().connect(addr).await.unwrap();
+        let client = ProofClient::new(stream).request_timeout(Duration::from_secs(10));
+        let mut keys = vec![vec![0xff, 0]];
+        keys.extend((0..20000u32).map(|i| vec![0xfe, (i >> 8) as u8, i as u8]));
+
+        // 同时发出多个请求，客户端发送请求的时候，服务端在等待客户端读取响应
+        let requests = (0..4).map(|_| client.get_verified_many::<String>(&root_hash, &keys));
+        for values in futures::future::join_all(requests).await {
+            let values = values.unwrap();
+            assert_eq!(values.len(), keys.len());
+            assert_eq!(values[0].as_ref(), Some(&large_value));
+            assert!(values[1..].iter().all(|value| value.is_none()));
+        }
+    }
+
+    #[tokio::test]
+    async fn proof_client_rejects_invalid_proof() {
+        let (snapshot, root_hash) = snapshot();
+        // 服务端篡改 proof 里的数据
+        let addr = start(snapshot.clone(), |mut response| {
+            let proof = match &mut response.body {
+                Some(response::Body::Proof(proof_response)) => &mut proof_response.proof,
+                Some(response::Body::Batch(batch_response)) => &mut batch_response.proof,
+                None => unreachable!(),
+            };
+            let last = proof.len() - 1;
+            proof[last] ^= 1;
+            response
+        })
+        .await;
+        let client = ProofClient::connect(addr).await.unwrap();
+        let result = client.get_verified::<String>(&root_hash, [0xff, 1]).await;
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+        let result = client
+            .get_verified_many::<String>(&root_hash, &[[0xff, 1], [0xff, 2]])
+            .await;
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 服务端声称 key 不存在，但是没有提供 proof
+        let addr = start(snapshot.clone(), |response| Response {
+            request_id: response.request_id,
+            ..Response::from(ProofResponse::from((false, Proof::new())))
+        })
+        .await;
+        let client = ProofClient::connect(addr).await.unwrap();
+        let result = client.get_verified::<String>(&root_hash, [0xff, 1]).await;
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 批量请求的 proof 缺少部分 key 的路径
+        let addr = start(snapshot, |response| Response {
+            request_id: response.request_id,
+            ..Response::from(ProofBatchResponse::from(Proof::new()))
+        })
+        .await;
+        let client = ProofClient::connect(addr).await.unwrap();
+        let result = client
+            .get_verified_many::<String>(&root_hash, &[[0xff, 1], [0xff, 9]])
+            .await;
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+    }
+
+    /// 与 Blake2bHasher 不同的 hash 函数
+    struct DoubleBlake2bHasher;
+
+    impl TrieHasher for DoubleBlake2bHasher {
+        fn hash(data: &[u8]) -> HashValue {
+            Blake2bHasher::hash(&Blake2bHasher::hash(data))
+        }
+    }
+
+    #[tokio::test]
+    async fn proof_client_with_hasher_works() {
+        // 服务端的 trie 使用另一个 hash 函数
+        let mut trie = MemoryTrie::<Vec<u8>, String, DoubleBlake2bHasher>::new();
+        for i in 0..4u8 {
+            trie.insert(vec![0xff, i], format!("value{i:02}")).unwrap();
+        }
+        let root_hash = trie.commit().unwrap().unwrap();
+        let addr = start(trie.snapshot().unwrap(), |response| response).await;
+        let client = ProofClient::connect(addr).await.unwrap();
+
+        // 使用默认的 hash 函数无法验证服务端返回的 proof
+        let result = client.get_verified::<String>(&root_hash, [0xff, 1]).await;
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 使用与服务端相同的 hash 函数
+        let client = client.with_hasher::<DoubleBlake2bHasher>();
+        let value = client.get_verified::<String>(&root_hash, [0xff, 1]).await;
+        assert_eq!(value.unwrap(), Some("value01".to_string()));
+        let values = client
+            .get_verified_many::<String>(&root_hash, &[[0xff, 2], [0xff, 9]])
+            .await;
+        assert_eq!(values.unwrap(), vec![Some("value02".to_string()), None]);
+    }
+
+    #[tokio::test]
+    async fn proof_client_timeout_works() {
+        // 只接收连接，从不返回响应的服务端
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepting = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+        let client = ProofClient::connect(addr)
+            .await
+            .unwrap()
+            .request_timeout(Duration::from_millis(50));
+        let result = client.get_verified::<String>(&[1; 32], [0xff]).await;
+        assert!(matches!(result, Err(TrieError::Timeout)));
+
+        // 服务端关闭连接之后，请求返回网络错误
+        drop(accepting.await.unwrap());
+        let result = client.get_verified::<String>(&[1; 32], [0xff]).await;
+        assert!(matches!(result, Err(TrieError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn proof_client_pending_limit_works() {
+        // 只接收请求，从不返回响应的服务端，把收到的请求数量发给测试
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (received, mut receiver) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+            while let Some(Ok(_)) = framed.next().await {
+                received.send(()).unwrap();
+            }
+        });
+
+        let client = ProofClient::connect(addr).await.unwrap();
+        let short = client.clone().request_timeout(Duration::from_millis(200));
+        let requests = (0..MAX_PENDING_REQUESTS).map(|_| {
+            let client = short.clone();
+            async move { client.get_verified::<String>(&[1; 32], [0xff]).await }
+        });
+        let requests = tokio::spawn(futures::future::join_all(requests));
+        for _ in 0..MAX_PENDING_REQUESTS {
+            receiver.recv().await.unwrap();
+        }
+
+        // 等待中的请求达到上限之后，新的请求不会被发送
+        let long = client.request_timeout(Duration::from_secs(10));
+        tokio::spawn(async move { long.get_verified::<String>(&[1; 32], [0xff]).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(receiver.try_recv().is_err());
+
+        // 超时的请求不再占用名额，新的请求被发送
+        for result in requests.await.unwrap() {
+            assert!(matches!(result, Err(TrieError::Timeout)));
+        }
+        receiver.recv().await.unwrap();
+    }
+}
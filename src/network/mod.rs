@@ -1,2 +1,139 @@
+/// 自动验证 proof 的 TCP 轻客户端
+#[cfg(feature = "client")]
+pub mod client;
 mod pb;
+/// 可以嵌入到服务里的 TCP proof 服务端
+#[cfg(feature = "server")]
+pub mod server;
+
 pub use pb::*;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::database::Database;
+use crate::{HashValue, TrieSnapshot};
+
+/// 处理一个请求，单个 key 的请求生成 compact proof，批量请求生成多个 key 共享的 multi proof
+/// 出错时返回带有错误状态的响应，客户端可以知道失败的原因，请求没有 body 时返回 STATUS_INVALID_REQUEST
+/// 响应的 request_id 与请求相同。不依赖传输方式，libp2p 等其他协议的服务端也可以直接使用
+pub fn process_proof_request<D, V>(
+    snapshot: &TrieSnapshot<D, Vec<u8>, V>,
+    request: Request,
+) -> Response
+where
+    D: Database,
+    V: Serialize + DeserializeOwned,
+{
+    let body = match request.body {
+        Some(request::Body::Proof(proof_request)) => {
+            // 从 proof_request 中获取 hash_value 和 key，key 可以是任意的二进制数据
+            let result = <(HashValue, Vec<u8>)>::try_from(proof_request)
+                // root hash 不存在时返回 TrieError::UnknownRoot
+                .and_then(|(hash_value, key)| snapshot.at(hash_value).get_compact_proof(&key));
+            response::Body::Proof(match result {
+                Ok(proof) => ProofResponse::from(proof),
+                Err(e) => ProofResponse::from(e),
+            })
+        }
+        Some(request::Body::Batch(batch_request)) => {
+            let result = <(HashValue, Vec<Vec<u8>>)>::try_from(batch_request)
+                .and_then(|(hash_value, keys)| snapshot.at(hash_value).get_multi_proof(&keys));
+            response::Body::Batch(match result {
+                Ok(proof) => ProofBatchResponse::from(proof),
+                Err(e) => ProofBatchResponse::from(e),
+            })
+        }
+        None => return invalid_request(request.request_id, "missing request body".to_string()),
+    };
+    Response {
+        request_id: request.request_id,
+        body: Some(body),
+    }
+}
+
+/// 无法处理的请求的响应，body 是 status 为 STATUS_INVALID_REQUEST 的 ProofResponse
+fn invalid_request(request_id: u64, error: String) -> Response {
+    Response {
+        request_id,
+        body: Some(response::Body::Proof(ProofResponse {
+            status: Status::InvalidRequest as i32,
+            error,
+            ..Default::default()
+        })),
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::database::MemoryDatabase;
+    use crate::{verify_multi_proof, Blake2bHasher, MemoryTrie, Trie, Verified};
+
+    /// 准备一个 trie 的快照，以及它的 root hash，服务端和客户端的测试共用
+    pub(crate) fn snapshot() -> (TrieSnapshot<MemoryDatabase, Vec<u8>, String>, HashValue) {
+        let mut trie = MemoryTrie::<Vec<u8>, String>::new();
+        for i in 0..8u8 {
+            trie.insert(vec![0xff, i], format!("value{i:02}")).unwrap();
+        }
+        let root_hash = trie.commit().unwrap().unwrap();
+        (trie.snapshot().unwrap(), root_hash)
+    }
+
+    #[test]
+    fn process_proof_request_works() {
+        let (snapshot, root_hash) = snapshot();
+        let request = Request {
+            request_id: 3,
+            ..Request::from(ProofRequest::from((root_hash, vec![0xff, 9])))
+        };
+        let response = process_proof_request(&snapshot, request);
+        assert_eq!(response.request_id, 3);
+        let proof_response = ProofResponse::try_from(response).unwrap();
+        let (exists, _) = proof_response.into_proof::<Blake2bHasher>().unwrap();
+        assert!(!exists);
+
+        // root hash 的长度不对
+        let request = Request {
+            request_id: 4,
+            ..Request::from(ProofRequest {
+                root_hash: vec![0; 31],
+                key: vec![0xff],
+            })
+        };
+        let response = process_proof_request(&snapshot, request);
+        assert_eq!(response.request_id, 4);
+        let proof_response = ProofResponse::try_from(response).unwrap();
+        assert_eq!(proof_response.status(), Status::InvalidHashLength);
+
+        // 批量请求返回多个 key 共享的 proof
+        let keys = vec![vec![0xff, 1], vec![0xff, 9]];
+        let request = Request {
+            request_id: 5,
+            ..Request::from(ProofBatchRequest::from((root_hash, keys.clone())))
+        };
+        let response = process_proof_request(&snapshot, request);
+        assert_eq!(response.request_id, 5);
+        let proof = ProofBatchResponse::try_from(response)
+            .unwrap()
+            .into_proof::<Blake2bHasher>()
+            .unwrap();
+        let values = verify_multi_proof::<Blake2bHasher, _, String>(&root_hash, &proof, &keys);
+        assert_eq!(
+            values.unwrap(),
+            vec![
+                Verified::Present("value01".to_string()),
+                Verified::ProvablyAbsent
+            ]
+        );
+
+        // 没有 body 的请求
+        let request = Request {
+            request_id: 6,
+            body: None,
+        };
+        let response = process_proof_request(&snapshot, request);
+        assert_eq!(response.request_id, 6);
+        let proof_response = ProofResponse::try_from(response).unwrap();
+        assert_eq!(proof_response.status(), Status::InvalidRequest);
+    }
+}
@@ -1,3 +1,50 @@
+/// TCP 连接上传输的请求，单个 key 和批量请求共用同一个 request_id
+#[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Request {
+    /// 客户端分配的请求 id，服务端原样返回在响应里
+    /// 同一个连接上的请求可能乱序返回，客户端用它匹配请求和响应
+    #[prost(uint64, tag = "1")]
+    pub request_id: u64,
+    #[prost(oneof = "request::Body", tags = "2, 3")]
+    pub body: ::core::option::Option<request::Body>,
+}
+/// Nested message and enum types in `Request`.
+pub mod request {
+    #[derive(PartialOrd)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Body {
+        #[prost(message, tag = "2")]
+        Proof(super::ProofRequest),
+        #[prost(message, tag = "3")]
+        Batch(super::ProofBatchRequest),
+    }
+}
+/// 与 Request 对应的响应，body 的类型与请求相同
+/// 服务端无法解码请求或者请求没有 body 时，body 是 status 为 STATUS_INVALID_REQUEST 的 ProofResponse
+#[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Response {
+    #[prost(uint64, tag = "1")]
+    pub request_id: u64,
+    #[prost(oneof = "response::Body", tags = "2, 3")]
+    pub body: ::core::option::Option<response::Body>,
+}
+/// Nested message and enum types in `Response`.
+pub mod response {
+    #[derive(PartialOrd)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Body {
+        #[prost(message, tag = "2")]
+        Proof(super::ProofResponse),
+        #[prost(message, tag = "3")]
+        Batch(super::ProofBatchResponse),
+    }
+}
 #[derive(PartialOrd)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -13,6 +60,83 @@ pub struct ProofRequest {
 pub struct ProofResponse {
     #[prost(bool, tag = "1")]
     pub exists: bool,
-    #[prost(bytes = "vec", tag = "2")]
-    pub proof_db: ::prost::alloc::vec::Vec<u8>,
+    /// 使用 Proof::encode 编码的紧凑格式 proof
+    #[prost(bytes = "vec", tag = "3")]
+    pub proof: ::prost::alloc::vec::Vec<u8>,
+    #[prost(enumeration = "Status", tag = "4")]
+    pub status: i32,
+    /// 出错时的错误信息
+    #[prost(string, tag = "5")]
+    pub error: ::prost::alloc::string::String,
+}
+/// 批量请求同一个 root hash 下多个 key 的 proof
+#[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ProofBatchRequest {
+    #[prost(bytes = "vec", tag = "1")]
+    pub root_hash: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes = "vec", repeated, tag = "2")]
+    pub keys: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+#[derive(PartialOrd)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ProofBatchResponse {
+    /// 所有 key 共享的紧凑格式 proof，路径上的节点只出现一次
+    #[prost(bytes = "vec", tag = "1")]
+    pub proof: ::prost::alloc::vec::Vec<u8>,
+    #[prost(enumeration = "Status", tag = "2")]
+    pub status: i32,
+    /// 出错时的错误信息
+    #[prost(string, tag = "3")]
+    pub error: ::prost::alloc::string::String,
+}
+/// 请求的处理结果，不是 STATUS_OK 时响应里只有 status 和 error 是有效的
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Status {
+    Ok = 0,
+    /// 服务端的数据库里没有请求的 root hash，可能从未被提交过或者已经被裁剪
+    UnknownRoot = 1,
+    /// 请求里的 root hash 不是 32 字节
+    InvalidHashLength = 2,
+    /// 请求里的 key 无效
+    InvalidKey = 3,
+    /// 服务端内部错误，比如读取数据库失败
+    Internal = 4,
+    /// 服务端没有在限定的时间内处理完请求，客户端可以稍后重试
+    Timeout = 5,
+    /// 服务端无法解码请求或者请求没有 body，无法解码时响应的 request_id 为 0
+    InvalidRequest = 6,
+}
+impl Status {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Status::Ok => "STATUS_OK",
+            Status::UnknownRoot => "STATUS_UNKNOWN_ROOT",
+            Status::InvalidHashLength => "STATUS_INVALID_HASH_LENGTH",
+            Status::InvalidKey => "STATUS_INVALID_KEY",
+            Status::Internal => "STATUS_INTERNAL",
+            Status::Timeout => "STATUS_TIMEOUT",
+            Status::InvalidRequest => "STATUS_INVALID_REQUEST",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "STATUS_OK" => Some(Self::Ok),
+            "STATUS_UNKNOWN_ROOT" => Some(Self::UnknownRoot),
+            "STATUS_INVALID_HASH_LENGTH" => Some(Self::InvalidHashLength),
+            "STATUS_INVALID_KEY" => Some(Self::InvalidKey),
+            "STATUS_INTERNAL" => Some(Self::Internal),
+            "STATUS_TIMEOUT" => Some(Self::Timeout),
+            "STATUS_INVALID_REQUEST" => Some(Self::InvalidRequest),
+            _ => None,
+        }
+    }
 }
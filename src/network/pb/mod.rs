@@ -2,10 +2,14 @@ mod abi;
 
 pub use abi::*;
 
-use crate::{database::MemoryDatabase, HashValue, TrieError};
+use crate::{
+    trie::{Proof, TrieHasher},
+    HashValue, TrieError,
+};
 
-/// 将 ProofRequest 转换为 (HashValue, String)
-impl TryFrom<ProofRequest> for (HashValue, String) {
+/// 将 ProofRequest 转换为 (HashValue, Vec<u8>)
+/// key 可以是任意的二进制数据，比如 hash 之后的存储前缀，服务端使用以 Vec<u8> 为 key 的 trie 生成 proof
+impl TryFrom<ProofRequest> for (HashValue, Vec<u8>) {
     type Error = TrieError;
 
     fn try_from(v: ProofRequest) -> Result<Self, Self::Error> {
@@ -14,39 +18,339 @@ impl TryFrom<ProofRequest> for (HashValue, String) {
             .try_into()
             .map_err(|_| TrieError::InvalidHashValue)?;
 
-        let key = String::from_utf8(v.key).map_err(|_| TrieError::InvalidKey)?;
-        Ok((hash_value, key))
+        Ok((hash_value, v.key))
     }
 }
 
-/// 将 (HashValue, String) 转换为 ProofRequest
-impl From<(HashValue, String)> for ProofRequest {
-    fn from(v: (HashValue, String)) -> Self {
+/// 将 (HashValue, K) 转换为 ProofRequest，K 可以是 String、Vec<u8> 等任意可以转换为字节的 key
+impl<K: AsRef<[u8]>> From<(HashValue, K)> for ProofRequest {
+    fn from(v: (HashValue, K)) -> Self {
         ProofRequest {
             root_hash: v.0.to_vec(),
-            key: v.1.into_bytes(),
+            key: v.1.as_ref().to_vec(),
         }
     }
 }
 
-/// 将 ProofResponse 转换为 (bool, MemoryDatabase)
-impl TryFrom<ProofResponse> for (bool, MemoryDatabase) {
+/// 将 ProofRequest 包装为 Request，request_id 为 0，需要在同一个连接上同时发出多个请求时由调用者设置
+impl From<ProofRequest> for Request {
+    fn from(v: ProofRequest) -> Self {
+        Request {
+            request_id: 0,
+            body: Some(request::Body::Proof(v)),
+        }
+    }
+}
+
+/// 将 ProofBatchRequest 包装为 Request，与上面相同，request_id 为 0
+impl From<ProofBatchRequest> for Request {
+    fn from(v: ProofBatchRequest) -> Self {
+        Request {
+            request_id: 0,
+            body: Some(request::Body::Batch(v)),
+        }
+    }
+}
+
+/// 将 ProofResponse 包装为 Response，request_id 为 0，需要由调用者设置为请求的 request_id
+impl From<ProofResponse> for Response {
+    fn from(v: ProofResponse) -> Self {
+        Response {
+            request_id: 0,
+            body: Some(response::Body::Proof(v)),
+        }
+    }
+}
+
+/// 将 ProofBatchResponse 包装为 Response，与上面相同，request_id 为 0
+impl From<ProofBatchResponse> for Response {
+    fn from(v: ProofBatchResponse) -> Self {
+        Response {
+            request_id: 0,
+            body: Some(response::Body::Batch(v)),
+        }
+    }
+}
+
+/// 从 Response 里取出单个 key 的 ProofResponse
+/// 服务端无法处理请求时 body 可能是另一种响应，这时返回其中的错误，body 的类型不对时返回 TrieError::Network
+impl TryFrom<Response> for ProofResponse {
     type Error = TrieError;
 
-    fn try_from(v: ProofResponse) -> Result<(bool, MemoryDatabase), Self::Error> {
-        let memory_db = bincode::deserialize(v.proof_db.as_slice())?;
-        Ok((v.exists, memory_db))
+    fn try_from(v: Response) -> Result<Self, Self::Error> {
+        match v.body {
+            Some(response::Body::Proof(proof_response)) => Ok(proof_response),
+            Some(response::Body::Batch(batch_response)) => {
+                check_status(batch_response.status, batch_response.error)?;
+                Err(unexpected_response())
+            }
+            None => Err(unexpected_response()),
+        }
     }
 }
 
-/// 将 (bool, MemoryDatabase) 转换为 ProofResponse
-impl TryFrom<(bool, MemoryDatabase)> for ProofResponse {
+/// 从 Response 里取出 ProofBatchResponse，与上面相同，body 是 ProofResponse 时返回其中的错误
+impl TryFrom<Response> for ProofBatchResponse {
     type Error = TrieError;
 
-    fn try_from(v: (bool, MemoryDatabase)) -> Result<Self, Self::Error> {
-        Ok(ProofResponse {
+    fn try_from(v: Response) -> Result<Self, Self::Error> {
+        match v.body {
+            Some(response::Body::Batch(batch_response)) => Ok(batch_response),
+            Some(response::Body::Proof(proof_response)) => {
+                check_status(proof_response.status, proof_response.error)?;
+                Err(unexpected_response())
+            }
+            None => Err(unexpected_response()),
+        }
+    }
+}
+
+fn unexpected_response() -> TrieError {
+    TrieError::Network("unexpected response body".to_string())
+}
+
+/// 将 TrieError 转换为 Status，客户端可以据此区分 root hash 不存在、超时和服务端内部错误
+impl From<&TrieError> for Status {
+    fn from(e: &TrieError) -> Self {
+        match e {
+            TrieError::UnknownRoot => Status::UnknownRoot,
+            TrieError::InvalidHashValue => Status::InvalidHashLength,
+            TrieError::InvalidKey => Status::InvalidKey,
+            TrieError::Timeout => Status::Timeout,
+            _ => Status::Internal,
+        }
+    }
+}
+
+/// 将响应里的 status 和 error 转换为 TrieError，STATUS_OK 时返回 Ok
+/// 服务端内部错误和无法识别的 status 都转换为 TrieError::Database，服务端无法解码请求时转换为 TrieError::Network
+fn check_status(status: i32, error: String) -> Result<(), TrieError> {
+    match Status::from_i32(status) {
+        Some(Status::Ok) => Ok(()),
+        Some(Status::UnknownRoot) => Err(TrieError::UnknownRoot),
+        Some(Status::InvalidHashLength) => Err(TrieError::InvalidHashValue),
+        Some(Status::InvalidKey) => Err(TrieError::InvalidKey),
+        Some(Status::Internal) => Err(TrieError::Database(error)),
+        Some(Status::Timeout) => Err(TrieError::Timeout),
+        Some(Status::InvalidRequest) => Err(TrieError::Network(error)),
+        None => Err(TrieError::Database(format!(
+            "unknown status {status}: {error}"
+        ))),
+    }
+}
+
+impl ProofResponse {
+    /// 转换为 (bool, Proof)，H 是服务端的 trie 计算节点 hash 使用的 hash 函数
+    /// 服务端返回错误时，转换为对应的 TrieError
+    pub fn into_proof<H: TrieHasher>(self) -> Result<(bool, Proof), TrieError> {
+        check_status(self.status, self.error)?;
+        let proof = Proof::decode::<H>(self.proof.as_slice())?;
+        Ok((self.exists, proof))
+    }
+}
+
+/// 将 (bool, Proof) 转换为 ProofResponse
+impl From<(bool, Proof)> for ProofResponse {
+    fn from(v: (bool, Proof)) -> Self {
+        ProofResponse {
             exists: v.0,
-            proof_db: bincode::serialize(&v.1)?,
-        })
+            proof: v.1.encode(),
+            status: Status::Ok as i32,
+            error: String::new(),
+        }
+    }
+}
+
+/// 将 TrieError 转换为表示错误的 ProofResponse
+impl From<TrieError> for ProofResponse {
+    fn from(e: TrieError) -> Self {
+        ProofResponse {
+            exists: false,
+            proof: Vec::new(),
+            status: Status::from(&e) as i32,
+            error: e.to_string(),
+        }
+    }
+}
+
+/// 将 ProofBatchRequest 转换为 (HashValue, Vec<Vec<u8>>)
+impl TryFrom<ProofBatchRequest> for (HashValue, Vec<Vec<u8>>) {
+    type Error = TrieError;
+
+    fn try_from(v: ProofBatchRequest) -> Result<Self, Self::Error> {
+        let hash_value: HashValue = v
+            .root_hash
+            .try_into()
+            .map_err(|_| TrieError::InvalidHashValue)?;
+
+        Ok((hash_value, v.keys))
+    }
+}
+
+/// 将 (HashValue, Vec<K>) 转换为 ProofBatchRequest
+impl<K: AsRef<[u8]>> From<(HashValue, Vec<K>)> for ProofBatchRequest {
+    fn from(v: (HashValue, Vec<K>)) -> Self {
+        ProofBatchRequest {
+            root_hash: v.0.to_vec(),
+            keys: v.1.iter().map(|key| key.as_ref().to_vec()).collect(),
+        }
+    }
+}
+
+impl ProofBatchResponse {
+    /// 转换为 Proof，与 ProofResponse 相同，H 是服务端的 trie 使用的 hash 函数
+    pub fn into_proof<H: TrieHasher>(self) -> Result<Proof, TrieError> {
+        check_status(self.status, self.error)?;
+        Proof::decode::<H>(self.proof.as_slice())
+    }
+}
+
+/// 将 Proof 转换为 ProofBatchResponse
+impl From<Proof> for ProofBatchResponse {
+    fn from(v: Proof) -> Self {
+        ProofBatchResponse {
+            proof: v.encode(),
+            status: Status::Ok as i32,
+            error: String::new(),
+        }
+    }
+}
+
+/// 将 TrieError 转换为表示错误的 ProofBatchResponse
+impl From<TrieError> for ProofBatchResponse {
+    fn from(e: TrieError) -> Self {
+        ProofBatchResponse {
+            proof: Vec::new(),
+            status: Status::from(&e) as i32,
+            error: e.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        verify_compact_proof, verify_multi_proof, Blake2bHasher, MemoryTrie, Trie, Verified,
+    };
+    use prost::Message;
+
+    /// 准备一个以二进制数据为 key 的 trie
+    fn binary_trie() -> (MemoryTrie<Vec<u8>, String>, HashValue) {
+        let mut trie = MemoryTrie::<Vec<u8>, String>::new();
+        // 不是合法 UTF-8 的 key
+        trie.insert(vec![0xff, 0x00, 0x01], "value01".to_string())
+            .unwrap();
+        trie.insert(vec![0xff, 0x00, 0x02], "value02".to_string())
+            .unwrap();
+        trie.insert(vec![0x80, 0xfe], "value03".to_string())
+            .unwrap();
+        let root_hash = trie.commit().unwrap().unwrap();
+        (trie, root_hash)
+    }
+
+    #[test]
+    fn proof_request_binary_key_works() {
+        let (mut trie, root_hash) = binary_trie();
+        let key = vec![0xff, 0x00, 0x02];
+
+        // 客户端构建请求，经过 protobuf 编解码之后，服务端得到原样的 key
+        let request = ProofRequest::from((root_hash, key.clone()));
+        let request = ProofRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        let (hash_value, request_key): (HashValue, Vec<u8>) = request.try_into().unwrap();
+        assert_eq!(hash_value, root_hash);
+        assert_eq!(request_key, key);
+
+        // 服务端生成 proof，客户端验证
+        let proof = trie.get_compact_proof(&hash_value, &request_key).unwrap();
+        let response = ProofResponse::from(proof);
+        let response = ProofResponse::decode(response.encode_to_vec().as_slice()).unwrap();
+        let (exists, proof) = response.into_proof::<Blake2bHasher>().unwrap();
+        assert!(exists);
+        let value = verify_compact_proof::<Blake2bHasher, _, String>(&root_hash, &proof, &key);
+        assert_eq!(value.unwrap(), Verified::Present("value02".to_string()));
+    }
+
+    #[test]
+    fn proof_response_error_works() {
+        let (mut trie, _) = binary_trie();
+
+        // 请求不存在的 root hash，服务端返回 STATUS_UNKNOWN_ROOT
+        let error = trie.get_compact_proof(&[1; 32], &vec![0xff]).unwrap_err();
+        let response = ProofResponse::from(error);
+        let response = ProofResponse::decode(response.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(response.status(), Status::UnknownRoot);
+        let result = response.into_proof::<Blake2bHasher>();
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+
+        // root hash 的长度不对
+        let request = ProofRequest {
+            root_hash: vec![0; 31],
+            key: vec![0xff],
+        };
+        let error = <(HashValue, Vec<u8>)>::try_from(request).unwrap_err();
+        let response = ProofBatchResponse::from(error);
+        assert_eq!(response.status(), Status::InvalidHashLength);
+        let result = response.into_proof::<Blake2bHasher>();
+        assert!(matches!(result, Err(TrieError::InvalidHashValue)));
+
+        // 服务端内部错误，错误信息被传递给客户端
+        let response = ProofResponse::from(TrieError::Database("disk failure".to_string()));
+        assert_eq!(response.status(), Status::Internal);
+        let result = response.into_proof::<Blake2bHasher>();
+        assert!(matches!(result, Err(TrieError::Database(e)) if e.contains("disk failure")));
+    }
+
+    #[test]
+    fn proof_batch_request_binary_key_works() {
+        let (mut trie, root_hash) = binary_trie();
+        let keys = vec![vec![0xff, 0x00, 0x01], vec![0x80, 0xfe], vec![0xc3, 0x28]];
+
+        let request = ProofBatchRequest::from((root_hash, keys.clone()));
+        let request = ProofBatchRequest::decode(request.encode_to_vec().as_slice()).unwrap();
+        let (hash_value, request_keys): (HashValue, Vec<Vec<u8>>) = request.try_into().unwrap();
+        assert_eq!(request_keys, keys);
+
+        let proof = trie.get_multi_proof(&hash_value, &request_keys).unwrap();
+        let response = ProofBatchResponse::from(proof);
+        let proof = response.into_proof::<Blake2bHasher>().unwrap();
+        let values = verify_multi_proof::<Blake2bHasher, _, String>(&root_hash, &proof, &keys);
+        assert_eq!(
+            values.unwrap(),
+            vec![
+                Verified::Present("value01".to_string()),
+                Verified::Present("value03".to_string()),
+                Verified::ProvablyAbsent,
+            ]
+        );
+    }
+
+    #[test]
+    fn response_body_works() {
+        let (mut trie, root_hash) = binary_trie();
+
+        // 请求经过 protobuf 编解码之后，request_id 和 body 都不变
+        let request = Request {
+            request_id: 5,
+            ..Request::from(ProofBatchRequest::from((root_hash, vec![vec![0xff]])))
+        };
+        let decoded = Request::decode(request.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(decoded, request);
+
+        // body 的类型与请求相同
+        let proof = trie.get_multi_proof(&root_hash, &[vec![0xff]]).unwrap();
+        let response = Response::from(ProofBatchResponse::from(proof));
+        assert!(ProofBatchResponse::try_from(response.clone()).is_ok());
+        let result = ProofResponse::try_from(response);
+        assert!(matches!(result, Err(TrieError::Network(_))));
+
+        // body 是另一种表示错误的响应时，返回其中的错误
+        let response = Response::from(ProofResponse::from(TrieError::UnknownRoot));
+        let result = ProofBatchResponse::try_from(response);
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+
+        // 没有 body 的响应
+        let result = ProofResponse::try_from(Response::default());
+        assert!(matches!(result, Err(TrieError::Network(_))));
     }
 }
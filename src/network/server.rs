@@ -0,0 +1,575 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{SinkExt, StreamExt};
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tokio_util::codec::{Framed, LengthDelimitedCodec};
+use tokio_util::sync::CancellationToken;
+
+use super::{
+    invalid_request, process_proof_request, request, response, ProofBatchResponse, ProofResponse,
+    Request, Response,
+};
+use crate::database::Database;
+use crate::{TrieError, TrieSnapshot};
+
+/// 每个连接默认同时处理的请求数量
+const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 16;
+/// 默认的请求超时时间
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// TCP proof 服务端
+///
+/// 每个请求和响应都是一个 protobuf 消息，使用 `LengthDelimitedCodec` 分帧，帧头是表示数据长度的 u32。
+/// 请求是 Request，body 可以是单个 key 的 ProofRequest 或者多个 key 的 ProofBatchRequest。
+/// 同一个连接上的请求最多同时处理 max_concurrent_requests 个，先处理完的请求先发送响应，
+/// 响应里带有请求的 request_id，客户端用它匹配请求和响应。
+/// 超过 request_timeout 还没有处理完的请求返回 STATUS_TIMEOUT，无法解码的请求返回 STATUS_INVALID_REQUEST。
+/// 发送一个响应超过 request_timeout 时关闭连接，客户端一直不读取响应时也不会阻塞 shutdown。
+///
+/// 克隆的服务端共享同一个关闭信号，在任意一个克隆上调用 shutdown 都会关闭服务端。
+///
+/// ```ignore
+/// let server = ProofServer::new().request_timeout(Duration::from_secs(3));
+/// let handle = server.clone();
+/// tokio::spawn(async move { server.serve(listener, snapshot).await });
+/// // ...
+/// handle.shutdown();
+/// ```
+#[derive(Debug, Clone)]
+pub struct ProofServer {
+    max_concurrent_requests: usize,
+    request_timeout: Duration,
+    shutdown: CancellationToken,
+}
+
+impl Default for ProofServer {
+    fn default() -> Self {
+        Self {
+            max_concurrent_requests: DEFAULT_MAX_CONCURRENT_REQUESTS,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            shutdown: CancellationToken::new(),
+        }
+    }
+}
+
+impl ProofServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置每个连接同时处理的请求数量，至少为 1
+    /// 超时的请求在阻塞线程里处理完之前仍然占用名额
+    pub fn max_concurrent_requests(mut self, limit: usize) -> Self {
+        self.max_concurrent_requests = limit.max(1);
+        self
+    }
+
+    /// 设置请求的超时时间
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// 关闭服务端：不再接收新的连接和请求，已经收到的请求处理完并发送响应之后，serve 返回
+    /// 发送响应超时的连接会被关闭，所以 serve 总是在有限的时间里返回
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// 在 listener 上接收连接，使用快照生成 proof，直到 shutdown 被调用
+    ///
+    /// 请求里的 root hash 通过 TrieSnapshot::at 切换，所以只要数据库里还有这个 root hash，
+    /// 快照创建之后提交的 root hash 也可以被请求。
+    /// 读写连接失败时只会关闭这个连接，无法解码的请求不会关闭连接；接收连接失败时返回错误。
+    pub async fn serve<D, V>(
+        &self,
+        listener: TcpListener,
+        snapshot: TrieSnapshot<D, Vec<u8>, V>,
+    ) -> io::Result<()>
+    where
+        D: Database + Send + Sync + 'static,
+        V: Serialize + DeserializeOwned + 'static,
+    {
+        let mut connections = JoinSet::new();
+        loop {
+            tokio::select! {
+                _ = self.shutdown.cancelled() => break,
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted?;
+                    connections.spawn(self.clone().process_stream(stream, snapshot.clone()));
+                }
+                // 回收已经断开的连接
+                Some(_) = connections.join_next(), if !connections.is_empty() => {}
+            }
+        }
+        // 等待所有连接发送完已经收到的请求的响应
+        while connections.join_next().await.is_some() {}
+        Ok(())
+    }
+
+    /// 处理一个连接，直到客户端断开或者服务端关闭
+    async fn process_stream<D, V>(
+        self,
+        stream: TcpStream,
+        snapshot: TrieSnapshot<D, Vec<u8>, V>,
+    ) -> io::Result<()>
+    where
+        D: Database + Send + Sync + 'static,
+        V: Serialize + DeserializeOwned + 'static,
+    {
+        let (mut writer, reader) = Framed::new(stream, LengthDelimitedCodec::new()).split();
+        // 收到关闭信号之后不再读取新的请求
+        let shutdown = self.shutdown.clone();
+        let requests = reader.take_until(async move { shutdown.cancelled().await });
+        // 超时的请求不再被等待，但是阻塞线程里的任务仍然在运行，
+        // 所以用信号量限制同时运行的任务数量，任务结束时才释放许可
+        let permits = Arc::new(Semaphore::new(self.max_concurrent_requests));
+        // buffer_unordered 最多同时处理 max_concurrent_requests 个请求，哪个请求先处理完就先输出哪个响应
+        let responses = requests
+            .map(|bytes| {
+                let snapshot = snapshot.clone();
+                let permits = permits.clone();
+                let request_timeout = self.request_timeout;
+                async move {
+                    // 读取连接失败时关闭连接
+                    let response = match Request::decode(bytes?) {
+                        Ok(request) => {
+                            process_with_timeout(snapshot, request, permits, request_timeout).await
+                        }
+                        // 无法解码的请求没有 request_id，返回 request_id 为 0 的错误响应，继续处理之后的请求
+                        Err(e) => invalid_request(0, e.to_string()),
+                    };
+                    Ok::<_, io::Error>(response)
+                }
+            })
+            .buffer_unordered(self.max_concurrent_requests);
+        futures::pin_mut!(responses);
+
+        // 只有这里向连接写入数据，客户端不读取响应时写入会一直阻塞，超时之后关闭连接
+        while let Some(response) = responses.next().await {
+            let bytes = Bytes::from(response?.encode_to_vec());
+            tokio::time::timeout(self.request_timeout, writer.send(bytes))
+                .await
+                .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "write response timeout"))??;
+        }
+        Ok(())
+    }
+}
+
+/// 获得 permits 的许可之后，在阻塞线程里生成 proof，等待许可和生成 proof 的总时间超时后返回 STATUS_TIMEOUT
+/// 超时的任务不会被取消，只是不再等待它的结果，许可在任务结束时才会被释放
+async fn process_with_timeout<D, V>(
+    snapshot: TrieSnapshot<D, Vec<u8>, V>,
+    request: Request,
+    permits: Arc<Semaphore>,
+    request_timeout: Duration,
+) -> Response
+where
+    D: Database + Send + Sync + 'static,
+    V: Serialize + DeserializeOwned + 'static,
+{
+    let request_id = request.request_id;
+    let batch = matches!(request.body, Some(request::Body::Batch(_)));
+    let task = async move {
+        // 信号量不会被关闭，获得许可不会失败
+        let permit = permits.acquire_owned().await.unwrap();
+        tokio::task::spawn_blocking(move || {
+            let response = process_proof_request(&snapshot, request);
+            drop(permit);
+            response
+        })
+        .await
+    };
+    let error = match tokio::time::timeout(request_timeout, task).await {
+        Ok(Ok(response)) => return response,
+        // 生成 proof 的任务 panic 了
+        Ok(Err(e)) => TrieError::Database(e.to_string()),
+        Err(_) => TrieError::Timeout,
+    };
+    // 错误响应的 body 与请求的类型相同
+    let body = if batch {
+        response::Body::Batch(ProofBatchResponse::from(error))
+    } else {
+        response::Body::Proof(ProofResponse::from(error))
+    };
+    Response {
+        request_id,
+        body: Some(body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::database::MemoryDatabase;
+    use crate::network::tests::snapshot;
+    use crate::network::{ProofBatchRequest, ProofRequest, Status};
+    use crate::{
+        verify_compact_proof, verify_multi_proof, Blake2bHasher, HashValue, MemoryTrie, Result,
+        Trie, Verified,
+    };
+
+    /// 读取数据很慢的数据库，用来测试超时和关闭，同时记录最多有多少个线程在同时读取
+    /// 每次开始读取时通知 started，测试可以据此知道请求已经开始处理
+    struct SlowDatabase {
+        db: MemoryDatabase,
+        delay: Duration,
+        running: AtomicUsize,
+        peak: AtomicUsize,
+        started: tokio::sync::Notify,
+    }
+
+    impl SlowDatabase {
+        fn new(db: MemoryDatabase, delay: Duration) -> Self {
+            Self {
+                db,
+                delay,
+                running: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+                started: tokio::sync::Notify::new(),
+            }
+        }
+
+        /// 最多有多少个线程在同时读取
+        fn peak(&self) -> usize {
+            self.peak.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Database for SlowDatabase {
+        fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            self.started.notify_one();
+            std::thread::sleep(self.delay);
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            self.db.get(key)
+        }
+
+        fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Result<()> {
+            self.db.insert(key, value)
+        }
+
+        fn exists(&self, key: &HashValue) -> Result<bool> {
+            self.db.exists(key)
+        }
+
+        fn remove(&mut self, key: &HashValue) -> Result<()> {
+            self.db.remove(key)
+        }
+
+        fn keys(&self) -> Result<Vec<HashValue>> {
+            self.db.keys()
+        }
+    }
+
+    /// 启动服务端，返回服务端、服务端的地址和 serve 的任务
+    async fn start<D>(
+        server: ProofServer,
+        snapshot: TrieSnapshot<D, Vec<u8>, String>,
+    ) -> (
+        ProofServer,
+        std::net::SocketAddr,
+        tokio::task::JoinHandle<io::Result<()>>,
+    )
+    where
+        D: Database + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handle = server.clone();
+        let serving = tokio::spawn(async move { server.serve(listener, snapshot).await });
+        (handle, addr, serving)
+    }
+
+    async fn connect(addr: std::net::SocketAddr) -> Framed<TcpStream, LengthDelimitedCodec> {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        Framed::new(stream, LengthDelimitedCodec::new())
+    }
+
+    fn request(root_hash: HashValue, key: Vec<u8>, request_id: u64) -> Request {
+        Request {
+            request_id,
+            ..Request::from(ProofRequest::from((root_hash, key)))
+        }
+    }
+
+    async fn send(framed: &mut Framed<TcpStream, LengthDelimitedCodec>, request: Request) {
+        framed
+            .send(Bytes::from(request.encode_to_vec()))
+            .await
+            .unwrap();
+    }
+
+    async fn recv(framed: &mut Framed<TcpStream, LengthDelimitedCodec>) -> Response {
+        let bytes = framed.next().await.unwrap().unwrap();
+        Response::decode(bytes).unwrap()
+    }
+
+    /// 单个 key 的请求的响应的 status
+    fn status(response: Response) -> Status {
+        ProofResponse::try_from(response).unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn proof_server_works() {
+        let (snapshot, root_hash) = snapshot();
+        let (server, addr, serving) = start(ProofServer::new(), snapshot).await;
+        let mut framed = connect(addr).await;
+
+        // 连续发送多个请求，响应可能乱序返回，用 request_id 匹配
+        for i in 0..8u8 {
+            send(&mut framed, request(root_hash, vec![0xff, i], i as u64)).await;
+        }
+        send(&mut framed, request([1; 32], vec![0xff], 8)).await;
+        let mut responses: Vec<_> = Vec::new();
+        for _ in 0..9 {
+            responses.push(recv(&mut framed).await);
+        }
+        responses.sort_by_key(|response| response.request_id);
+        // 不存在的 root hash
+        let response = responses.pop().unwrap();
+        assert_eq!(response.request_id, 8);
+        assert_eq!(status(response), Status::UnknownRoot);
+        for (i, response) in responses.into_iter().enumerate() {
+            assert_eq!(response.request_id, i as u64);
+            let proof_response = ProofResponse::try_from(response).unwrap();
+            let (exists, proof) = proof_response.into_proof::<Blake2bHasher>().unwrap();
+            assert!(exists);
+            let key = [0xff, i as u8];
+            let value = verify_compact_proof::<Blake2bHasher, _, String>(&root_hash, &proof, &key);
+            assert_eq!(value.unwrap(), Verified::Present(format!("value{i:02}")));
+        }
+
+        // 关闭服务端之后，serve 返回，连接被关闭
+        server.shutdown();
+        serving.await.unwrap().unwrap();
+        assert!(framed.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn proof_server_batch_works() {
+        let (snapshot, root_hash) = snapshot();
+        let (server, addr, serving) = start(ProofServer::new(), snapshot).await;
+        let mut framed = connect(addr).await;
+
+        // 批量请求和单个 key 的请求在同一个连接上发送，用 request_id 匹配
+        let keys = vec![vec![0xff, 1], vec![0xff, 9], vec![0xff, 3]];
+        let batch_request = Request {
+            request_id: 1,
+            ..Request::from(ProofBatchRequest::from((root_hash, keys.clone())))
+        };
+        send(&mut framed, batch_request).await;
+        send(&mut framed, request(root_hash, vec![0xff, 2], 2)).await;
+        let mut responses = vec![recv(&mut framed).await, recv(&mut framed).await];
+        responses.sort_by_key(|response| response.request_id);
+        assert_eq!(status(responses.pop().unwrap()), Status::Ok);
+
+        let proof = ProofBatchResponse::try_from(responses.pop().unwrap())
+            .unwrap()
+            .into_proof::<Blake2bHasher>()
+            .unwrap();
+        let values = verify_multi_proof::<Blake2bHasher, _, String>(&root_hash, &proof, &keys);
+        assert_eq!(
+            values.unwrap(),
+            vec![
+                Verified::Present("value01".to_string()),
+                Verified::ProvablyAbsent,
+                Verified::Present("value03".to_string()),
+            ]
+        );
+
+        server.shutdown();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn proof_server_out_of_order_works() {
+        let (snapshot, root_hash) = snapshot();
+        let db = SlowDatabase::new(snapshot.db().clone(), Duration::from_millis(50));
+        let snapshot = TrieSnapshot::new(Arc::new(db), Some(root_hash));
+        let (server, addr, serving) = start(ProofServer::new(), snapshot.clone()).await;
+        let mut framed = connect(addr).await;
+
+        // 第一个请求需要读取路径上的多个节点，第二个请求读取根节点就失败了，所以先返回
+        send(&mut framed, request(root_hash, vec![0xff, 1], 1)).await;
+        send(&mut framed, request([1; 32], vec![0xff], 2)).await;
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 2);
+        assert_eq!(status(response), Status::UnknownRoot);
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 1);
+        assert_eq!(status(response), Status::Ok);
+
+        // 每个连接只同时处理一个请求时，响应按照请求的顺序返回
+        server.shutdown();
+        serving.await.unwrap().unwrap();
+        let server = ProofServer::new().max_concurrent_requests(1);
+        let (server, addr, serving) = start(server, snapshot).await;
+        let mut framed = connect(addr).await;
+        send(&mut framed, request(root_hash, vec![0xff, 1], 1)).await;
+        send(&mut framed, request([1; 32], vec![0xff], 2)).await;
+        assert_eq!(recv(&mut framed).await.request_id, 1);
+        assert_eq!(recv(&mut framed).await.request_id, 2);
+
+        server.shutdown();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn proof_server_timeout_works() {
+        let (snapshot, root_hash) = snapshot();
+        let db = SlowDatabase::new(snapshot.db().clone(), Duration::from_millis(100));
+        let snapshot = TrieSnapshot::new(Arc::new(db), Some(root_hash));
+        let server = ProofServer::new().request_timeout(Duration::from_millis(10));
+        let (server, addr, serving) = start(server, snapshot).await;
+        let mut framed = connect(addr).await;
+
+        send(&mut framed, request(root_hash, vec![0xff, 1], 7)).await;
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 7);
+        let proof_response = ProofResponse::try_from(response).unwrap();
+        assert_eq!(proof_response.status(), Status::Timeout);
+        let result = proof_response.into_proof::<Blake2bHasher>();
+        assert!(matches!(result, Err(TrieError::Timeout)));
+
+        // 批量请求超时时返回批量请求的响应
+        let keys = vec![vec![0xff, 1], vec![0xff, 2]];
+        let batch_request = Request {
+            request_id: 8,
+            ..Request::from(ProofBatchRequest::from((root_hash, keys)))
+        };
+        send(&mut framed, batch_request).await;
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 8);
+        let result =
+            ProofBatchResponse::try_from(response).map(|v| v.into_proof::<Blake2bHasher>());
+        assert!(matches!(result, Ok(Err(TrieError::Timeout))));
+
+        server.shutdown();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn proof_server_timeout_keeps_concurrency_limit_works() {
+        let (snapshot, root_hash) = snapshot();
+        let db = SlowDatabase::new(snapshot.db().clone(), Duration::from_millis(20));
+        let snapshot = TrieSnapshot::new(Arc::new(db), Some(root_hash));
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let server = ProofServer::new()
+                .max_concurrent_requests(2)
+                .request_timeout(Duration::from_millis(5));
+            let (server, addr, serving) = start(server, snapshot.clone()).await;
+            let mut framed = connect(addr).await;
+
+            // 所有请求都超时了，但是超时的请求仍然占用名额，同时运行的任务不会超过 2 个
+            for i in 0..6u8 {
+                send(&mut framed, request(root_hash, vec![0xff, i], i as u64)).await;
+            }
+            for _ in 0..6 {
+                assert_eq!(status(recv(&mut framed).await), Status::Timeout);
+            }
+            server.shutdown();
+            serving.await.unwrap().unwrap();
+        });
+        // drop runtime 时会等待阻塞线程里的任务结束
+        drop(runtime);
+        assert!(snapshot.db().peak() <= 2);
+    }
+
+    #[tokio::test]
+    async fn proof_server_invalid_request_works() {
+        let (snapshot, root_hash) = snapshot();
+        let (server, addr, serving) = start(ProofServer::new(), snapshot).await;
+        let mut framed = connect(addr).await;
+
+        // 无法解码的请求返回 STATUS_INVALID_REQUEST，request_id 为 0
+        framed.send(Bytes::from_static(&[0xff])).await.unwrap();
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 0);
+        let proof_response = ProofResponse::try_from(response).unwrap();
+        assert_eq!(proof_response.status(), Status::InvalidRequest);
+        let result = proof_response.into_proof::<Blake2bHasher>();
+        assert!(matches!(result, Err(TrieError::Network(_))));
+
+        // 没有 body 的请求也返回 STATUS_INVALID_REQUEST，request_id 与请求相同
+        let empty_request = Request {
+            request_id: 2,
+            body: None,
+        };
+        send(&mut framed, empty_request).await;
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 2);
+        assert_eq!(status(response), Status::InvalidRequest);
+
+        // 连接没有被关闭，之后的请求仍然会被处理
+        send(&mut framed, request(root_hash, vec![0xff, 1], 1)).await;
+        let response = recv(&mut framed).await;
+        assert_eq!(response.request_id, 1);
+        assert_eq!(status(response), Status::Ok);
+
+        server.shutdown();
+        serving.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn proof_server_graceful_shutdown_works() {
+        let (snapshot, root_hash) = snapshot();
+        let db = SlowDatabase::new(snapshot.db().clone(), Duration::from_millis(50));
+        let snapshot = TrieSnapshot::new(Arc::new(db), Some(root_hash));
+        let (server, addr, serving) = start(ProofServer::new(), snapshot.clone()).await;
+        let mut framed = connect(addr).await;
+
+        // 请求正在处理时关闭服务端，已经收到的请求仍然会返回响应
+        send(&mut framed, request(root_hash, vec![0xff, 1], 0)).await;
+        snapshot.db().started.notified().await;
+        server.shutdown();
+        let proof_response = ProofResponse::try_from(recv(&mut framed).await).unwrap();
+        let (exists, _) = proof_response.into_proof::<Blake2bHasher>().unwrap();
+        assert!(exists);
+        assert!(framed.next().await.is_none());
+        serving.await.unwrap().unwrap();
+
+        // 关闭之后不再接收新的连接
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn proof_server_shutdown_write_timeout_works() {
+        // value 很大，但是响应不超过 LengthDelimitedCodec 的帧的最大长度 8MB
+        let mut trie = MemoryTrie::<Vec<u8>, String>::new();
+        trie.insert(vec![0xff, 1], "a".repeat(6 << 20)).unwrap();
+        let root_hash = trie.commit().unwrap().unwrap();
+        let db = SlowDatabase::new(trie.db_ref().clone(), Duration::ZERO);
+        let snapshot = TrieSnapshot::new(Arc::new(db), Some(root_hash));
+        let server = ProofServer::new().request_timeout(Duration::from_millis(200));
+        let (server, addr, serving) = start(server, snapshot.clone()).await;
+
+        // 客户端的接收缓冲区很小，并且从不读取响应
+        let socket = tokio::net::TcpSocket::new_v4().unwrap();
+        socket.set_recv_buffer_size(4096).unwrap();
+        let stream = socket.connect(addr).await.unwrap();
+        let mut framed = Framed::new(stream, LengthDelimitedCodec::new());
+        // 几个响应加起来远远超过连接的缓冲区
+        for i in 0..4 {
+            send(&mut framed, request(root_hash, vec![0xff, 1], i)).await;
+        }
+        snapshot.db().started.notified().await;
+
+        // 发送响应超时之后连接被关闭，serve 可以返回
+        server.shutdown();
+        let result = tokio::time::timeout(Duration::from_secs(10), serving).await;
+        result.unwrap().unwrap().unwrap();
+        drop(framed);
+    }
+}
@@ -0,0 +1,237 @@
+use serde::de::DeserializeOwned;
+
+use super::node::{Branch, Extension, Node, TrieNode, TrieNodeLink};
+use super::{KeccakHasher, TrieHasher};
+use crate::database::Database;
+use crate::{HashValue, NibbleSlice, Result, TrieError};
+
+/// Ethereum 的 RLP(Recursive Length Prefix) 编码
+/// RLP 只有两种数据: 字节串和列表，列表的元素可以是字节串或者列表
+mod rlp;
+
+/// 计算 root_node 对应的 trie 的 Ethereum 兼容的 root hash
+/// 空的 trie 的 root hash 为 Keccak-256(RLP(""))，根节点的编码即使小于 32 字节也会计算 hash
+/// 这里只计算 root hash，trie 保存的节点和生成的 proof 仍然使用 trie 自身的编码
+///
+/// 节点按照 Ethereum 黄皮书附录 D 的规则编码:
+///
+/// - 叶子节点: RLP([hex_prefix(rest_of_key, true), value])
+/// - 扩展节点: RLP([hex_prefix(partial_key, false), 分支节点的引用])
+/// - 分支节点: RLP([16 个子节点的引用, value])
+///
+/// 编码小于 32 字节的子节点直接内嵌在父节点里，否则引用子节点编码的 Keccak-256。
+/// 节点里保存的是 bincode 序列化的 V，编码时先反序列化为 V，再使用 V 的原始字节作为 value
+pub(crate) fn root_hash<V>(db: &impl Database, root_node: &TrieNodeLink) -> Result<HashValue>
+where
+    V: DeserializeOwned + AsRef<[u8]>,
+{
+    let encoded = encode_link::<V>(db, root_node)?;
+    Ok(KeccakHasher::hash(&encoded))
+}
+
+/// 编码 TrieNodeLink 指向的节点，TrieNodeLink::HashValue 指向的节点会从数据库里读取
+fn encode_link<V>(db: &impl Database, link: &TrieNodeLink) -> Result<Vec<u8>>
+where
+    V: DeserializeOwned + AsRef<[u8]>,
+{
+    match link {
+        TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+            encode_node::<V>(db, trie_node)
+        }
+        TrieNodeLink::HashValue(hash_value) => {
+            let bin_node = db.get(hash_value)?.ok_or(TrieError::Database(format!(
+                "Value for `{}` not found",
+                hex::encode(hash_value)
+            )))?;
+            let trie_node = TrieNode::decode(&bin_node)?;
+            encode_node::<V>(db, &trie_node)
+        }
+        // 空节点编码为空字节串
+        TrieNodeLink::Empty => Ok(rlp::encode_bytes(&[])),
+    }
+}
+
+/// 编码一个节点
+fn encode_node<V>(db: &impl Database, trie_node: &TrieNode) -> Result<Vec<u8>>
+where
+    V: DeserializeOwned + AsRef<[u8]>,
+{
+    let items = match trie_node {
+        TrieNode::Node(Node { rest_of_key, value }) => vec![
+            rlp::encode_bytes(&hex_prefix(rest_of_key, true)),
+            encode_value::<V>(value)?,
+        ],
+        TrieNode::Extension(Extension {
+            partial_key,
+            branch,
+        }) => vec![
+            rlp::encode_bytes(&hex_prefix(partial_key, false)),
+            reference(encode_link::<V>(db, branch)?),
+        ],
+        TrieNode::Branch(Branch { children, value }) => {
+            let mut items = Vec::with_capacity(17);
+            for child in children.iter() {
+                items.push(reference(encode_link::<V>(db, child)?));
+            }
+            items.push(match value {
+                Some(value) => encode_value::<V>(value)?,
+                None => rlp::encode_bytes(&[]),
+            });
+            items
+        }
+    };
+    Ok(rlp::encode_list(&items))
+}
+
+/// 获得父节点对子节点的引用，小于 32 字节的编码直接内嵌，否则使用编码的 hash
+fn reference(encoded: Vec<u8>) -> Vec<u8> {
+    if encoded.len() < 32 {
+        encoded
+    } else {
+        rlp::encode_bytes(&KeccakHasher::hash(&encoded))
+    }
+}
+
+/// 将 bincode 序列化的 value 反序列化为 V，然后编码 V 的原始字节
+fn encode_value<V>(bin_value: &[u8]) -> Result<Vec<u8>>
+where
+    V: DeserializeOwned + AsRef<[u8]>,
+{
+    let value: V = bincode::deserialize(bin_value)?;
+    Ok(rlp::encode_bytes(value.as_ref()))
+}
+
+/// hex-prefix 编码，将 nibble 路径编码为字节
+/// 第一个 nibble 是标志: 叶子节点加 2，nibble 数量为奇数时加 1，
+/// 奇数时第一个 nibble 与标志组成第一个字节，偶数时标志后面补一个 0
+fn hex_prefix(nibbles: &NibbleSlice, leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (nibbles.len() % 2) as u8;
+    let mut buf = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        buf.push((flag << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        buf.push(flag << 4);
+        nibbles
+    };
+    buf.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{memory_trie::MemoryTrie, Trie};
+
+    #[test]
+    fn hex_prefix_works() {
+        // 来自 Ethereum 黄皮书的例子
+        assert_eq!(hex_prefix(&[1, 2, 3, 4, 5], false), vec![0x11, 0x23, 0x45]);
+        assert_eq!(
+            hex_prefix(&[0, 1, 2, 3, 4, 5], false),
+            vec![0x00, 0x01, 0x23, 0x45]
+        );
+        assert_eq!(
+            hex_prefix(&[0, 15, 1, 12, 11, 8], true),
+            vec![0x20, 0x0f, 0x1c, 0xb8]
+        );
+        assert_eq!(
+            hex_prefix(&[15, 1, 12, 11, 8], true),
+            vec![0x3f, 0x1c, 0xb8]
+        );
+        assert_eq!(hex_prefix(&[], true), vec![0x20]);
+    }
+
+    /// 解析测试向量里的字符串，以 0x 开头的是十六进制数据
+    fn parse(s: &str) -> Vec<u8> {
+        match s.strip_prefix("0x") {
+            Some(hex_str) => hex::decode(hex_str).unwrap(),
+            None => s.as_bytes().to_vec(),
+        }
+    }
+
+    /// 依次执行测试向量里的操作，value 为 null 时删除 key，然后检查 root hash
+    fn check_vector(name: &str, ops: &[(Vec<u8>, Option<Vec<u8>>)], root: &str) {
+        let mut trie = MemoryTrie::<Vec<u8>, Vec<u8>>::new();
+        for (key, value) in ops.iter() {
+            match value {
+                Some(value) => trie.insert(key.clone(), value.clone()).unwrap(),
+                None => {
+                    trie.remove(key).unwrap();
+                }
+            }
+        }
+        let expected = parse(root);
+        // 未提交的 trie
+        let root_hash = trie.eth_root_hash().unwrap();
+        assert_eq!(root_hash.to_vec(), expected, "{name}");
+        // 提交之后，节点从数据库里读取
+        trie.commit().unwrap();
+        let root_hash = trie.eth_root_hash().unwrap();
+        assert_eq!(root_hash.to_vec(), expected, "{name}");
+    }
+
+    /// 运行 trieanyorder.json 格式的测试向量，数据的插入顺序不影响 root hash
+    fn check_any_order(vectors: &serde_json::Value) {
+        for (name, vector) in vectors.as_object().unwrap() {
+            let mut ops: Vec<_> = vector["in"]
+                .as_object()
+                .unwrap()
+                .iter()
+                .map(|(key, value)| (parse(key), Some(parse(value.as_str().unwrap()))))
+                .collect();
+            let root = vector["root"].as_str().unwrap();
+            check_vector(name, &ops, root);
+            // 倒序插入
+            ops.reverse();
+            check_vector(name, &ops, root);
+        }
+    }
+
+    /// 运行 trietest.json 格式的测试向量，按顺序插入和删除数据
+    fn check_in_order(vectors: &serde_json::Value) {
+        for (name, vector) in vectors.as_object().unwrap() {
+            let ops: Vec<_> = vector["in"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|op| {
+                    let key = parse(op[0].as_str().unwrap());
+                    let value = op[1].as_str().map(parse);
+                    (key, value)
+                })
+                .collect();
+            check_vector(name, &ops, vector["root"].as_str().unwrap());
+        }
+    }
+
+    // 来自 ethereum/tests 的 TrieTests/trieanyorder.json
+    #[test]
+    fn eth_trie_any_order_works() {
+        let vectors = serde_json::from_str(include_str!("testdata/trieanyorder.json")).unwrap();
+        check_any_order(&vectors);
+    }
+
+    // 来自 ethereum/tests 的 TrieTests/trietest.json，只包括其中的一部分测试向量
+    #[test]
+    fn eth_trie_works() {
+        let vectors = serde_json::from_str(include_str!("testdata/trietest.json")).unwrap();
+        check_in_order(&vectors);
+    }
+
+    // ETHEREUM_TESTS_DIR 设置为 ethereum/tests 仓库的 TrieTests 目录时，运行其中完整的测试向量，没有设置时跳过
+    // 比如 ETHEREUM_TESTS_DIR=/path/to/ethereum/tests/TrieTests cargo test --features keccak
+    #[test]
+    fn eth_upstream_vectors_works() {
+        let dir = match std::env::var("ETHEREUM_TESTS_DIR") {
+            Ok(dir) => std::path::PathBuf::from(dir),
+            Err(_) => return,
+        };
+        let read = |name: &str| {
+            let json = std::fs::read_to_string(dir.join(name)).unwrap();
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()
+        };
+        check_any_order(&read("trieanyorder.json"));
+        check_in_order(&read("trietest.json"));
+    }
+}
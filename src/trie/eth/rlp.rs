@@ -0,0 +1,89 @@
+/// 编码字节串
+/// 单个小于 0x80 的字节编码为自身，其他字节串编码为长度前缀加上数据
+pub fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut buf = encode_length(bytes.len(), 0x80);
+    buf.extend_from_slice(bytes);
+    buf
+}
+
+/// 编码列表，items 里是已经编码好的元素
+pub fn encode_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let len = items.iter().map(Vec::len).sum();
+    let mut buf = encode_length(len, 0xc0);
+    for item in items.iter() {
+        buf.extend_from_slice(item);
+    }
+    buf
+}
+
+/// 编码长度前缀，字节串的 offset 为 0x80，列表的 offset 为 0xc0
+/// 长度小于 56 时前缀只有一个字节，否则前缀为 offset + 55 + 长度的字节数，后面跟着大端序的长度
+fn encode_length(len: usize, offset: u8) -> Vec<u8> {
+    if len < 56 {
+        return vec![offset + len as u8];
+    }
+    let len_bytes = len.to_be_bytes();
+    // 去掉长度前面的 0
+    let skip = len_bytes.iter().take_while(|byte| **byte == 0).count();
+    let len_bytes = &len_bytes[skip..];
+    let mut buf = Vec::with_capacity(1 + len_bytes.len());
+    buf.push(offset + 55 + len_bytes.len() as u8);
+    buf.extend_from_slice(len_bytes);
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 来自 Ethereum 的 RLP 测试向量 rlptest.json
+    #[test]
+    fn rlp_bytes_works() {
+        assert_eq!(encode_bytes(b""), vec![0x80]);
+        assert_eq!(encode_bytes(&[0x00]), vec![0x00]);
+        assert_eq!(encode_bytes(&[0x7f]), vec![0x7f]);
+        assert_eq!(encode_bytes(&[0x80]), vec![0x81, 0x80]);
+        assert_eq!(hex::encode(encode_bytes(b"dog")), "83646f67");
+
+        // 55 字节是短字节串的最大长度
+        let short = b"Lorem ipsum dolor sit amet, consectetur adipisicing eli";
+        assert_eq!(hex::encode(&encode_bytes(short)[..1]), "b7");
+        let long = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        assert_eq!(hex::encode(&encode_bytes(long)[..2]), "b838");
+        assert_eq!(&encode_bytes(long)[2..], long);
+
+        let long = vec![0; 1024];
+        assert_eq!(hex::encode(&encode_bytes(&long)[..3]), "b90400");
+    }
+
+    #[test]
+    fn rlp_list_works() {
+        assert_eq!(encode_list(&[]), vec![0xc0]);
+        let items: Vec<_> = [b"dog", b"god", b"cat"]
+            .iter()
+            .map(|item| encode_bytes(*item))
+            .collect();
+        assert_eq!(
+            hex::encode(encode_list(&items)),
+            "cc83646f6783676f6483636174"
+        );
+        // ["zw", [4], 1]
+        let items = vec![
+            encode_bytes(b"zw"),
+            encode_list(&[encode_bytes(&[4])]),
+            encode_bytes(&[1]),
+        ];
+        assert_eq!(hex::encode(encode_list(&items)), "c6827a77c10401");
+        // 56 字节以上的列表
+        let items: Vec<_> = [b"asdf", b"qwer", b"zxcv"]
+            .iter()
+            .cycle()
+            .take(12)
+            .map(|item| encode_bytes(*item))
+            .collect();
+        assert_eq!(hex::encode(&encode_list(&items)[..2]), "f83c");
+    }
+}
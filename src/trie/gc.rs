@@ -0,0 +1,107 @@
+use std::collections::{HashMap, HashSet};
+
+use super::node::TrieNode;
+use super::{load_latest_root, prune, version, TrieHasher};
+use crate::database::{write_in_batch, Database, RESERVED_KEYS};
+use crate::{HashValue, Result, TrieError};
+
+/// 垃圾回收的结果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GcStats {
+    /// 删除的节点数量
+    pub nodes: usize,
+    /// 删除的数据的字节数，包括节点和节点的引用计数等不再需要的记录
+    pub bytes: usize,
+}
+
+/// 标记-清除垃圾回收，是引用计数之外的另一种删除节点的方式
+///
+/// 从 live_roots 和最近一次提交的 root hash 出发，标记所有能通过 TrieNodeLink::HashValue 访问到的节点，
+/// 然后删除数据库里其他所有的 key，包括不再被引用的节点，以及 commit 中途崩溃时留下的节点。
+///
+/// 被标记的节点的引用计数会被重新计算，不可访问的固定 root hash 和版本记录会被删除，
+/// 所以 gc 之后仍然可以继续使用 prune 和 unpin。
+/// 最近一次提交的 root hash 总是被保留，即使它不在 live_roots 里，重新打开数据库时仍然可以从它继续。
+///
+/// 如果 live_roots 引用的节点不在数据库里，那么在删除任何数据之前返回 TrieError::Database。
+/// 固定 root hash、版本记录和引用计数的重写以及清除都在同一个批量写入里完成，
+/// 中途失败时不会留下只完成一半的修改。
+///
+/// H 是 trie 计算节点 hash 使用的 hash 函数，只用于统计被删除的节点数量
+pub fn gc<H>(db: &mut impl Database, live_roots: &[HashValue]) -> Result<GcStats>
+where
+    H: TrieHasher,
+{
+    // 标记所有可以访问到的节点，同时统计每个节点被其他节点引用的次数
+    let mut marked = HashSet::new();
+    let mut refcounts: HashMap<HashValue, u64> = HashMap::new();
+    let mut stack = live_roots.to_vec();
+    stack.extend(load_latest_root(db)?);
+    while let Some(hash_value) = stack.pop() {
+        if !marked.insert(hash_value) {
+            continue;
+        }
+        let bin_node = db.get(&hash_value)?.ok_or(TrieError::Database(format!(
+            "Value for `{}` not found",
+            hex::encode(hash_value)
+        )))?;
+        let trie_node = TrieNode::decode(&bin_node)?;
+        for child_hash in trie_node.child_hashes() {
+            *refcounts.entry(child_hash).or_default() += 1;
+            stack.push(child_hash);
+        }
+    }
+
+    // 所有的修改在同一个批量写入里完成
+    write_in_batch(db, |db| {
+        // 只保留仍然可以访问的固定 root hash，固定的次数也计入引用计数
+        let pinned_roots: Vec<_> = prune::load_pinned_roots(db)?
+            .into_iter()
+            .filter(|root_hash| marked.contains(root_hash))
+            .collect();
+        for root_hash in pinned_roots.iter() {
+            *refcounts.entry(*root_hash).or_default() += 1;
+        }
+        prune::save_pinned_roots(db, &pinned_roots)?;
+        // 只保留 root hash 仍然可以访问的版本记录
+        let mut live_keys: HashSet<HashValue> = RESERVED_KEYS.into_iter().collect();
+        let mut removed_versions = Vec::new();
+        for version in version::load_versions(db)? {
+            match version.root_hash {
+                Some(root_hash) if !marked.contains(&root_hash) => {
+                    removed_versions.push(version.number)
+                }
+                _ => {
+                    live_keys.insert(version::version_key(version.number));
+                }
+            }
+        }
+        version::remove_versions(db, &removed_versions)?;
+
+        // 重新写入被标记的节点的引用计数
+        for hash_value in marked.iter() {
+            let count = refcounts.get(hash_value).copied().unwrap_or_default();
+            prune::set_refcount(db, hash_value, count)?;
+            live_keys.insert(*hash_value);
+            live_keys.insert(prune::refcount_key(hash_value));
+        }
+
+        // 清除其他所有的 key
+        let mut stats = GcStats::default();
+        for key in db.keys()? {
+            if live_keys.contains(&key) {
+                continue;
+            }
+            if let Some(value) = db.get(&key)? {
+                // 节点以自身的 hash 作为 key 保存
+                if H::hash(&value) == key {
+                    stats.nodes += 1;
+                }
+                stats.bytes += value.len();
+            }
+            db.remove(&key)?;
+        }
+
+        Ok(stats)
+    })
+}
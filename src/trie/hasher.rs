@@ -0,0 +1,82 @@
+use super::util;
+use crate::HashValue;
+
+/// Trie 使用的 hash 函数
+///
+/// 节点以自身编码的 hash 作为 key 保存在数据库里，root hash 也是根节点编码的 hash，
+/// 所以同样的数据使用不同的 hash 函数会得到不同的 root hash。
+/// 生成和验证 proof 的双方必须使用同一个 hash 函数。
+///
+/// 数据库的 key 和节点里的 hash 都是 32 字节的 HashValue，所以 hash 函数的输出必须是 32 字节，
+/// 输出更长的 hash 函数需要截断到 32 字节，比如 Blake2b-512 需要改为 Blake2b-256
+pub trait TrieHasher {
+    /// 计算 data 的 hash
+    fn hash(data: &[u8]) -> HashValue;
+}
+
+/// 32 字节的 Blake2b，默认的 hash 函数，与 Substrate 的 Blake2-256 相同
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Blake2bHasher;
+
+impl TrieHasher for Blake2bHasher {
+    fn hash(data: &[u8]) -> HashValue {
+        util::hash(data)
+    }
+}
+
+/// Keccak-256，Ethereum 使用的 hash 函数
+#[cfg(feature = "keccak")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeccakHasher;
+
+#[cfg(feature = "keccak")]
+impl TrieHasher for KeccakHasher {
+    fn hash(data: &[u8]) -> HashValue {
+        use sha3::{Digest, Keccak256};
+        Keccak256::digest(data).into()
+    }
+}
+
+/// SHA-256
+#[cfg(feature = "sha2")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sha256Hasher;
+
+#[cfg(feature = "sha2")]
+impl TrieHasher for Sha256Hasher {
+    fn hash(data: &[u8]) -> HashValue {
+        use sha2::{Digest, Sha256};
+        Sha256::digest(data).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blake2b_hasher_works() {
+        assert_eq!(
+            hex::encode(Blake2bHasher::hash(b"")),
+            "0e5751c026e543b2e8ab2eb06099daa1d1e5df47778f7787faab45cdf12fe3a8"
+        );
+    }
+
+    #[cfg(feature = "keccak")]
+    #[test]
+    fn keccak_hasher_works() {
+        assert_eq!(
+            hex::encode(KeccakHasher::hash(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_hasher_works() {
+        assert_eq!(
+            hex::encode(Sha256Hasher::hash(b"")),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+
+use super::node::{Branch, Extension, Node, TrieNode, TrieNodeLink};
+use super::util;
+use crate::database::Database;
+use crate::{NibbleSlice, NibbleVec, Result};
+
+/// Trie 的迭代器，按 key 的字典序返回 trie 里所有的 key-value
+///
+/// 迭代器使用深度优先的方式遍历 trie，遇到 TrieNodeLink::HashValue 时才从数据库中读取节点，
+/// 所以不会一次性把整个 trie 加载到内存中
+pub struct TrieIterator<'a, D, V> {
+    db: &'a D,
+    // 待遍历的节点，以及从根节点到该节点的 nibble 路径
+    stack: Vec<(NibbleVec, TrieNodeLink)>,
+    // V 只在 Iterator 的实现里使用, 使用 PhantomData 来避免编译器报错
+    _v: PhantomData<V>,
+}
+
+impl<'a, D, V> TrieIterator<'a, D, V> {
+    pub fn new(db: &'a D, root_node: TrieNodeLink) -> Self {
+        Self {
+            db,
+            stack: vec![(NibbleVec::new(), root_node)],
+            _v: PhantomData,
+        }
+    }
+}
+
+impl<'a, D, V> TrieIterator<'a, D, V>
+where
+    D: Database,
+{
+    /// 创建一个只遍历 key 以 prefix_nb 开头的数据的迭代器
+    /// 先从根节点向下找到 prefix_nb 对应的子树，之后只遍历这棵子树
+    pub fn with_prefix(
+        db: &'a D,
+        root_node: TrieNodeLink,
+        prefix_nb: &NibbleSlice,
+    ) -> Result<Self> {
+        let mut iter = Self::new(db, TrieNodeLink::Empty);
+        // 从根节点到当前节点的 nibble 路径
+        let mut path = NibbleVec::new();
+        // prefix_nb 中还没有匹配的部分
+        let mut rest_of_prefix = prefix_nb;
+        let mut trie_node_link = root_node;
+
+        loop {
+            // prefix_nb 已经全部匹配，当前节点下的所有数据都以 prefix_nb 开头
+            if rest_of_prefix.is_empty() {
+                iter.stack = vec![(path, trie_node_link)];
+                return Ok(iter);
+            }
+
+            match trie_node_link.resolve(db)? {
+                // 叶子节点，只有 rest_of_key 以剩余的 prefix 开头时才匹配
+                Some(TrieNode::Node(node)) => {
+                    if node.rest_of_key.starts_with(rest_of_prefix) {
+                        iter.stack = vec![(path, node.into())];
+                    }
+                    return Ok(iter);
+                }
+                // 扩展节点，如果剩余的 prefix 以 partial_key 开头，继续向下查找
+                Some(TrieNode::Extension(extension))
+                    if rest_of_prefix.starts_with(&extension.partial_key) =>
+                {
+                    rest_of_prefix = &rest_of_prefix[extension.partial_key.len()..];
+                    path.extend_from_slice(&extension.partial_key);
+                    trie_node_link = extension.branch;
+                }
+                // 扩展节点，如果 prefix 在 partial_key 中间结束，那么整个扩展节点都匹配
+                Some(TrieNode::Extension(extension)) => {
+                    if extension.partial_key.starts_with(rest_of_prefix) {
+                        iter.stack = vec![(path, extension.into())];
+                    }
+                    return Ok(iter);
+                }
+                // 分支节点，根据剩余 prefix 的第一个 nibble 选择 child
+                Some(TrieNode::Branch(mut branch)) => {
+                    let (idx, rest) = rest_of_prefix.split_at(1);
+                    rest_of_prefix = rest;
+                    path.push(idx[0]);
+                    trie_node_link = std::mem::take(&mut branch.children[idx[0] as usize]);
+                }
+                // 空节点，没有匹配的数据
+                None => return Ok(iter),
+            }
+        }
+    }
+
+    /// 创建一个从 start_nb 开始遍历的迭代器，只返回 key 大于等于 start_nb 的数据
+    /// 从根节点沿着 start_nb 的路径向下查找，路径右侧的兄弟节点会被放入栈中等待遍历
+    pub fn with_start(db: &'a D, root_node: TrieNodeLink, start_nb: &NibbleSlice) -> Result<Self> {
+        let mut iter = Self::new(db, TrieNodeLink::Empty);
+        iter.stack.clear();
+        // 从根节点到当前节点的 nibble 路径
+        let mut path = NibbleVec::new();
+        // start_nb 中还没有匹配的部分
+        let mut rest_of_start = start_nb;
+        let mut trie_node_link = root_node;
+
+        loop {
+            // start_nb 已经全部匹配，当前节点下的所有数据都大于等于 start_nb
+            if rest_of_start.is_empty() {
+                iter.stack.push((path, trie_node_link));
+                return Ok(iter);
+            }
+
+            match trie_node_link.resolve(db)? {
+                // 叶子节点，只有 rest_of_key 大于等于剩余的 start 时才需要遍历
+                Some(TrieNode::Node(node)) => {
+                    if node.rest_of_key.as_slice() >= rest_of_start {
+                        iter.stack.push((path, node.into()));
+                    }
+                    return Ok(iter);
+                }
+                // 扩展节点，如果剩余的 start 以 partial_key 开头，继续向下查找
+                Some(TrieNode::Extension(extension))
+                    if rest_of_start.starts_with(&extension.partial_key) =>
+                {
+                    rest_of_start = &rest_of_start[extension.partial_key.len()..];
+                    path.extend_from_slice(&extension.partial_key);
+                    trie_node_link = extension.branch;
+                }
+                // 扩展节点，partial_key 大于剩余的 start 时，整个扩展节点都需要遍历，否则都不需要
+                Some(TrieNode::Extension(extension)) => {
+                    if extension.partial_key.as_slice() > rest_of_start {
+                        iter.stack.push((path, extension.into()));
+                    }
+                    return Ok(iter);
+                }
+                // 分支节点，索引大于剩余 start 第一个 nibble 的 child 都需要遍历，
+                // 分支节点的 value 小于 start，不需要遍历
+                Some(TrieNode::Branch(mut branch)) => {
+                    let (idx, rest) = rest_of_start.split_at(1);
+                    for (i, child) in branch.children.iter_mut().enumerate().rev() {
+                        if i as u8 > idx[0] && !matches!(child, TrieNodeLink::Empty) {
+                            let mut child_path = path.clone();
+                            child_path.push(i as u8);
+                            iter.stack.push((child_path, std::mem::take(child)));
+                        }
+                    }
+                    rest_of_start = rest;
+                    path.push(idx[0]);
+                    trie_node_link = std::mem::take(&mut branch.children[idx[0] as usize]);
+                }
+                // 空节点，没有需要遍历的数据
+                None => return Ok(iter),
+            }
+        }
+    }
+}
+
+impl<'a, D, V> TrieIterator<'a, D, V>
+where
+    V: DeserializeOwned,
+{
+    /// 将 nibble 路径转换回 key，并反序列化 value
+    fn decode(key_nb: NibbleVec, bin_value: Vec<u8>) -> Result<(Vec<u8>, V)> {
+        let key = util::convert_nibbles_to_bytes(&key_nb);
+        let value = bincode::deserialize(&bin_value)?;
+        Ok((key, value))
+    }
+}
+
+impl<'a, D, V> Iterator for TrieIterator<'a, D, V>
+where
+    D: Database,
+    V: DeserializeOwned,
+{
+    type Item = Result<(Vec<u8>, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, trie_node_link)) = self.stack.pop() {
+            // 如果是 HashValue，那么从数据库中读取节点
+            let trie_node = match trie_node_link.resolve(self.db) {
+                Ok(Some(trie_node)) => trie_node,
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            };
+
+            match trie_node {
+                // 叶子节点，路径加上 rest_of_key 就是完整的 key
+                TrieNode::Node(Node { rest_of_key, value }) => {
+                    return Some(Self::decode([path, rest_of_key].concat(), value));
+                }
+                // 扩展节点，将 partial_key 加入路径后继续遍历 branch
+                TrieNode::Extension(Extension {
+                    partial_key,
+                    branch,
+                }) => self.stack.push(([path, partial_key].concat(), branch)),
+                // 分支节点，children 逆序入栈，这样索引小的 child 会先被遍历
+                TrieNode::Branch(Branch { children, value }) => {
+                    for (idx, child) in children.into_iter().enumerate().rev() {
+                        if !matches!(child, TrieNodeLink::Empty) {
+                            let mut child_path = path.clone();
+                            child_path.push(idx as u8);
+                            self.stack.push((child_path, child));
+                        }
+                    }
+                    // 分支节点的 value 对应的 key 是所有 children 的 key 的前缀，所以要先返回
+                    if let Some(value) = value {
+                        return Some(Self::decode(path, value));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
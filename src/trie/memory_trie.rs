@@ -2,42 +2,64 @@ use std::marker::PhantomData;
 
 use serde::{de::DeserializeOwned, Serialize};
 
-use super::{node::TrieNodeLink, Trie};
-use crate::database::MemoryDatabase;
+use super::{node::TrieNodeLink, Blake2bHasher, Trie, TrieHasher};
+use crate::database::{Database, MemoryDatabase};
 
 /// 内存 Trie
-pub struct MemoryTrie<K, V> {
+///
+/// H 是计算节点 hash 使用的 hash 函数，默认为 Blake2bHasher
+/// D 是保存节点的数据库，默认为 MemoryDatabase，也可以使用 CachingDatabase 等包装过的数据库
+pub struct MemoryTrie<K, V, H = Blake2bHasher, D = MemoryDatabase> {
     root_node: TrieNodeLink,
-    db: MemoryDatabase,
+    db: D,
     dirty: bool,
-    // K, V 是 Trie trait 的方法里使用的, MemoryTrie 里没有使用
+    // K, V, H 是 Trie trait 的方法里使用的, MemoryTrie 里没有使用
     // 使用 PhantomData 来避免编译器报错
     _k: PhantomData<K>,
     _v: PhantomData<V>,
+    _h: PhantomData<H>,
 }
 
-impl<K, V> MemoryTrie<K, V> {
+impl<K, V, H> MemoryTrie<K, V, H> {
     pub fn new() -> Self {
+        Self::with_database(MemoryDatabase::new())
+    }
+}
+
+impl<K, V, H, D> MemoryTrie<K, V, H, D> {
+    /// 使用指定的数据库创建一个空的 trie
+    /// 比如 `CachingDatabase::new(MemoryDatabase::new(), capacity)`，读取节点时使用 LRU 缓存
+    pub fn with_database(db: D) -> Self {
         Self {
             root_node: TrieNodeLink::Empty,
-            db: MemoryDatabase::new(),
+            db,
             dirty: false,
             _k: PhantomData,
             _v: PhantomData,
+            _h: PhantomData,
         }
     }
 }
 
+impl<K, V, H> Default for MemoryTrie<K, V, H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // 通常只需要在实现时才约束泛型，定义结构体的时候不需要
 // 这样保持结构体的灵活性，同时我们也可以针对不同的约束给出不同的实现
-// 当然此处为了实现 Trie trait，我们必须要约束 K, V, 
+// 当然此处为了实现 Trie trait，我们必须要约束 K, V,
 // 所以这里的约束是必须的
-impl<K, V> Trie<K, V> for MemoryTrie<K, V>
+impl<K, V, H, D> Trie<K, V> for MemoryTrie<K, V, H, D>
 where
     K: AsRef<[u8]>,
     V: Serialize + DeserializeOwned,
+    H: TrieHasher,
+    D: Database,
 {
-    type Database = MemoryDatabase;
+    type Database = D;
+    type Hasher = H;
 
     fn dirty(&self) -> bool {
         self.dirty
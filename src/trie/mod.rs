@@ -1,13 +1,39 @@
 use crate::{
-    database::{Database, MemoryDatabase},
-    trie::node::{TrieNode, TrieNodeLink},
-    HashValue, Result, NibbleVec,
+    database::{write_in_batch, Database, MemoryDatabase, VerifyingDatabase, LATEST_ROOT_KEY},
+    trie::node::TrieNodeLink,
+    HashValue, NibbleVec, Result, TrieError,
 };
 use serde::{de::DeserializeOwned, Serialize};
+use std::sync::Arc;
 
+pub use gc::{gc, GcStats};
+#[cfg(feature = "keccak")]
+pub use hasher::KeccakHasher;
+#[cfg(feature = "sha2")]
+pub use hasher::Sha256Hasher;
+pub use hasher::{Blake2bHasher, TrieHasher};
+pub use iter::TrieIterator;
+pub use node::NODE_CODEC_VERSION;
+pub use proof::Proof;
+pub use range::RangeProof;
+pub use snapshot::TrieSnapshot;
+pub use version::Version;
+
+pub(crate) use node::TrieNode;
+
+#[cfg(feature = "keccak")]
+mod eth;
+pub mod gc;
+pub mod hasher;
+pub mod iter;
 pub mod memory_trie;
 mod node;
-mod util;
+pub mod proof;
+mod prune;
+pub mod range;
+pub mod snapshot;
+pub(crate) mod util;
+pub mod version;
 
 #[cfg(feature = "rocksdb")]
 pub mod rocksdb_trie;
@@ -21,6 +47,9 @@ where
     /// 数据库的类型
     type Database: Database;
 
+    /// 计算节点 hash 使用的 hash 函数
+    type Hasher: TrieHasher;
+
     /// 如果 trie 是 dirty 的，那么意味着数据还没有被提交
     fn dirty(&self) -> bool;
 
@@ -46,11 +75,13 @@ where
     fn insert(&mut self, key: K, value: V) -> Result<()> {
         // 将 key 转换为 nibble 形式
         let key_nb: NibbleVec = util::convert_bytes_to_nibbles(key.as_ref());
-        // 取得 trie 的根节点
-        let root_node = self.take_root_node();
         // 将 value 序列化
         let bin_node = bincode::serialize(&value)?;
-        // 将 key-value 插入到 trie 里，并返回新的根节点
+        // 取得 trie 的根节点
+        let root_node = self.take_root_node();
+        // 先读取路径上的节点，读取失败时放回原来的根节点，没有提交的数据不会丢失
+        let root_node = load_path(self, root_node, &key_nb)?;
+        // 将 key-value 插入到 trie 里，并返回新的根节点，路径上的节点都已经读取，不会再读取数据库
         let root_node = root_node.insert(self.db_mut(), &key_nb, bin_node)?;
         // 将新的根节点设置到 trie 里
         self.set_root_node(root_node);
@@ -70,23 +101,121 @@ where
             .map(|bin_node| bincode::deserialize(&bin_node).unwrap()))
     }
 
-    /// 把数据提交到数据库里，提交之后，节点数据会变成 hash，然后返回根 hash
-    fn commit(&mut self) -> Result<Option<HashValue>> {
-        // 获得根节点
+    /// 从 trie 里删除一个 key，返回被删除的 value
+    /// 删除之后 trie 会被重新整理，根 hash 与从未插入过这个 key 时相同
+    fn remove(&mut self, key: &K) -> Result<Option<V>> {
+        // 将 key 转换为 nibble 形式
+        let key_nb: NibbleVec = util::convert_bytes_to_nibbles(key.as_ref());
+        // 取得 trie 的根节点
         let root_node = self.take_root_node();
-        // 压缩根节点
-        let root_node = root_node.collapse(self.db_mut())?;
-        // 重新设置根节点
+        // 与 insert 相同，先读取路径上的节点
+        let root_node = load_path(self, root_node, &key_nb)?;
+        // 从 trie 里删除 key，并返回新的根节点和被删除的 value
+        let (root_node, bin_value) = root_node.remove(self.db_ref(), &key_nb)?;
+        // 将新的根节点设置到 trie 里
         self.set_root_node(root_node);
-        // 设置 dirty 标志
-        self.set_dirty(false);
 
-        match self.root_node() {
-            TrieNodeLink::HashValue(hash_value) => Ok(Some(hash_value.clone())),
-            TrieNodeLink::Empty => Ok(None),
-            // 压缩以后的 trie, 要么是Empty，要么是HashValue，不可能到这里
-            _ => unreachable!(),
+        match bin_value {
+            Some(bin_value) => {
+                // 有数据被删除，设置 dirty 标志
+                self.set_dirty(true);
+                Ok(Some(bincode::deserialize(&bin_value)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// 按 key 的字典序遍历 trie 里所有的 key-value
+    /// root 为 None 时遍历当前的根节点（包括还没有提交的数据），否则遍历指定 root hash 对应的 trie
+    fn iter(&self, root: Option<HashValue>) -> TrieIterator<'_, Self::Database, V> {
+        let root_node = match root {
+            Some(root_hash) => TrieNodeLink::HashValue(root_hash),
+            // 当前根节点可能包含未提交的节点，需要复制一份
+            None => self.root_node().clone(),
+        };
+        TrieIterator::new(self.db_ref(), root_node)
+    }
+
+    /// 按 key 的字典序遍历当前 trie 里所有以 prefix 开头的 key-value
+    /// 比如 prefix 为 `pellet01_state02` 时，只会返回这个 state 下的数据
+    fn iter_prefix(&self, prefix: &[u8]) -> Result<TrieIterator<'_, Self::Database, V>> {
+        // 将 prefix 转换为 nibble 形式
+        let prefix_nb = util::convert_bytes_to_nibbles(prefix);
+        TrieIterator::with_prefix(self.db_ref(), self.root_node().clone(), &prefix_nb)
+    }
+
+    /// 获得当前 trie 的只读快照，如果 trie 是 dirty 的，那么先提交
+    /// 快照持有数据库的一份克隆，RocksdbDatabase 的克隆共享同一个 rocksdb 实例，
+    /// MemoryDatabase 的克隆会复制所有的数据，之后 trie 的修改不会影响快照，
+    /// 所以对 MemoryTrie 创建快照的开销是 O(n)，n 为数据库里的条目数量，包括所有历史版本的节点
+    fn snapshot(&mut self) -> Result<TrieSnapshot<Self::Database, K, V>>
+    where
+        Self::Database: Clone,
+    {
+        // 如果 trie 是 dirty 的，那么先提交，快照只能绑定到已经提交的 root hash
+        let root_hash = match self.root_node() {
+            _ if self.dirty() => self.commit()?,
+            TrieNodeLink::HashValue(root_hash) => Some(*root_hash),
+            TrieNodeLink::Empty => None,
+            // 没有修改过的节点也可能已经被展开，提交以后会重新压缩成 hash
+            TrieNodeLink::TrieNode(_) | TrieNodeLink::Inline(_) => self.commit()?,
+        };
+        let db = Arc::new(self.db_ref().clone());
+        Ok(TrieSnapshot::new(db, root_hash))
+    }
+
+    /// 计算与 Ethereum 兼容的 root hash，包括还没有提交的数据
+    /// 节点使用 RLP 和 hex-prefix 编码，小于 32 字节的节点内嵌在父节点里，使用 Keccak-256 计算 hash，
+    /// 与 trie 自身的节点编码和 Hasher 无关。value 使用 V 的原始字节，比如 V 为 Vec<u8> 时与其他 Ethereum 客户端相同
+    /// 只提供 root hash，proof 仍然使用 trie 自身的节点编码，不能被 Ethereum 客户端验证
+    #[cfg(feature = "keccak")]
+    fn eth_root_hash(&self) -> Result<HashValue>
+    where
+        V: AsRef<[u8]>,
+    {
+        eth::root_hash::<V>(self.db_ref(), self.root_node())
+    }
+
+    /// 把数据提交到数据库里，提交之后，节点数据会变成 hash，然后返回根 hash
+    fn commit(&mut self) -> Result<Option<HashValue>> {
+        commit_with(self, None)
+    }
+
+    /// 只保留最近 keep_last 个不同的 root hash，删除不再被保留的 root hash 引用的节点，返回删除的节点数量
+    /// 最近一次提交的 root hash 总是被保留，对应的 root hash 已经被删除的版本记录也会被删除
+    /// 注意: 如果 trie 当前的根节点不在保留的 root hash 中，之后将无法再读取它
+    fn prune(&mut self, keep_last: usize) -> Result<usize> {
+        // 如果 trie 是 dirty 的，那么先提交，未提交的节点可能引用了将被删除的节点
+        if self.dirty() {
+            self.commit()?;
+        }
+        // 所有的删除在同一个批量写入里完成
+        write_in_batch(self.db_mut(), |db| {
+            let removed = prune::unpin_old_roots(db, keep_last)?;
+            prune::prune_versions(db)?;
+            Ok(removed)
+        })
+    }
+
+    /// 取消固定一次 root hash，删除不再被任何 root hash 引用的节点，返回删除的节点数量
+    /// 如果 root hash 没有被固定，返回 TrieError::InvalidHashValue
+    /// 如果是最近一次提交的 root hash 的最后一次固定，返回 TrieError::LatestRoot
+    fn unpin(&mut self, root_hash: &HashValue) -> Result<usize> {
+        // 如果 trie 是 dirty 的，那么先提交，未提交的节点可能引用了将被删除的节点
+        if self.dirty() {
+            self.commit()?;
         }
+        // 所有的删除在同一个批量写入里完成
+        write_in_batch(self.db_mut(), |db| {
+            let removed = prune::unpin(db, root_hash)?;
+            prune::prune_versions(db)?;
+            Ok(removed)
+        })
+    }
+
+    /// 获得最近一次提交的 root hash, 没有提交过或者提交时 trie 为空时返回 None
+    fn latest_root(&self) -> Result<Option<HashValue>> {
+        load_latest_root(self.db_ref())
     }
 
     /// 恢复到一个版本
@@ -98,7 +227,74 @@ where
         Ok(())
     }
 
+    /// 提交数据，并把提交后的 root hash 记录为 number 版本
+    /// 版本号必须大于已有的所有版本号，否则返回 TrieError::InvalidVersion
+    /// label 可以用来保存区块 hash 等信息，之后可以通过 version_by_label 查找
+    fn commit_as(&mut self, number: u64, label: Option<&[u8]>) -> Result<Option<HashValue>> {
+        let numbers = version::load_version_numbers(self.db_ref())?;
+        if numbers.last().is_some_and(|latest| *latest >= number) {
+            return Err(TrieError::InvalidVersion);
+        }
+        commit_with(self, Some((number, label)))
+    }
+
+    /// 切换到 number 版本，版本不存在时返回 TrieError::InvalidVersion
+    /// 没有提交的数据会被丢弃
+    fn checkout(&mut self, number: u64) -> Result<()> {
+        let version =
+            version::load_version(self.db_ref(), number)?.ok_or(TrieError::InvalidVersion)?;
+        match version.root_hash {
+            Some(root_hash) => self.revert(root_hash),
+            None => {
+                self.set_root_node(TrieNodeLink::Empty);
+                self.set_dirty(false);
+                Ok(())
+            }
+        }
+    }
+
+    /// 获得最新的版本
+    fn latest_version(&self) -> Result<Option<Version>> {
+        match version::load_version_numbers(self.db_ref())?.last() {
+            Some(number) => version::load_version(self.db_ref(), *number),
+            None => Ok(None),
+        }
+    }
+
+    /// 根据标签查找版本
+    fn version_by_label(&self, label: &[u8]) -> Result<Option<Version>> {
+        Ok(version::load_versions(self.db_ref())?
+            .into_iter()
+            .find(|version| version.label.as_deref() == Some(label)))
+    }
+
+    /// 获得所有的版本，按版本号从小到大排列
+    fn versions(&self) -> Result<Vec<Version>> {
+        version::load_versions(self.db_ref())
+    }
+
+    /// 获得多个 key 共享的 proof，路径上的节点在 proof 里只会出现一次
+    /// 同一个 root hash 下的多个 key 的路径通常会共享上层的节点，比逐个获取 proof 更小
+    fn get_multi_proof(&mut self, root_hash: &HashValue, keys: &[K]) -> Result<Proof> {
+        // 如果 trie 是 dirty 的，那么先提交
+        if self.dirty() {
+            self.commit()?;
+        }
+        collect_multi_proof(self.db_ref(), root_hash, keys)
+    }
+
+    /// 获得前缀 proof，proof 里包含了从根节点到前缀对应子树的路径上的所有节点，以及子树里所有的节点
+    /// 客户端可以使用 verify_prefix_proof 验证得到的数据就是以 prefix 开头的全部数据
+    fn get_prefix_proof(&mut self, root_hash: &HashValue, prefix: &[u8]) -> Result<MemoryDatabase> {
+        // 如果 trie 是 dirty 的，那么先提交
+        if self.dirty() {
+            self.commit()?;
+        }
+        collect_prefix_proof(self.db_ref(), root_hash, prefix)
+    }
+
     /// 获得 proof，proof 里包含了 key 的路径上的所有节点, bool 表示 key 是否存在， MemoryDatabase 是保存 proof 的数据库
+    /// 所有获得 proof 的方法在 root hash 不在数据库里时都返回 TrieError::UnknownRoot
     fn get_proof(&mut self, root_hash: &HashValue, key: &K) -> Result<(bool, MemoryDatabase)> {
         // 如果 trie 是 dirty 的，那么先提交
         if self.dirty() {
@@ -106,56 +302,366 @@ where
         }
         // 创建一个 MemoryDatabase
         let mut proof_db = MemoryDatabase::new();
-        // 从数据库里获得根节点的二进制数据
-        let bin_node_opt = self.db_ref().get(root_hash)?;
-        match bin_node_opt {
-            Some(bin_node) => {
-                // 反序列化根节点
-                let trie_node: TrieNode = bincode::deserialize(&bin_node)?;
-                // 将 key 转换为 nibble 形式
-                let key_nb = util::convert_bytes_to_nibbles(key.as_ref());
-                // 将根节点插入到 proof_db 里
-                proof_db.insert(*root_hash, bin_node)?;
-                // 通过查找key,将沿途路径上的节点收集到 proof_db 里
-                let exists = trie_node.get_proof(
-                    self.db_ref(),
-                    &mut proof_db,
-                    &key_nb,
-                )?;
-                Ok((exists, proof_db))
-            }
-            None => return Ok((false, proof_db)),
+        // 将 key 的路径上的节点收集到 proof_db 里
+        let exists = collect_proof(self.db_ref(), &mut proof_db, root_hash, key.as_ref())?;
+        Ok((exists, proof_db))
+    }
+
+    /// 获得范围 proof，返回 key 在 [start, end] 范围内的数据（最多 limit 条），
+    /// 以及范围左右边界路径上的节点，limit 至少为 1
+    /// 如果范围内的数据超过了 limit 条，那么 proof 只证明到最后一条数据为止，
+    /// 客户端可以从最后一条数据之后继续请求下一页
+    fn get_range_proof(
+        &mut self,
+        root_hash: &HashValue,
+        start: &[u8],
+        end: &[u8],
+        limit: usize,
+    ) -> Result<RangeProof<V>> {
+        // 如果 trie 是 dirty 的，那么先提交
+        if self.dirty() {
+            self.commit()?;
+        }
+        range::get_range_proof(self.db_ref(), root_hash, start, end, limit)
+    }
+
+    /// 获得紧凑格式的 proof，proof 里按从根节点到叶子节点的顺序保存了 key 的路径上的所有节点，
+    /// 不包含节点的 hash，比 get_proof 返回的 MemoryDatabase 更适合在网络上传输
+    fn get_compact_proof(&mut self, root_hash: &HashValue, key: &K) -> Result<(bool, Proof)> {
+        // 如果 trie 是 dirty 的，那么先提交
+        if self.dirty() {
+            self.commit()?;
         }
+        // 创建一个空的 Proof, 节点会按照收集的顺序保存
+        let mut proof = Proof::new();
+        // 将 key 的路径上的节点收集到 proof 里
+        let exists = collect_proof(self.db_ref(), &mut proof, root_hash, key.as_ref())?;
+        Ok((exists, proof))
     }
 }
 
-/// 验证 proof, 返回 key 对应的 value
-/// 如果 key 存在，那么返回 Some(value)，表示验证成功
-/// 如果 key 不存在，那么返回 None, 表明验证失败
-#[allow(dead_code)]
-pub fn verify_proof<K, V>(
-    root_hash: &HashValue,
-    proof_db: &impl Database,
-    key: &K,
-) -> Result<Option<V>>
+/// 提交 trie 里的数据，version 不为 None 时同时记录版本
+/// 新节点、引用计数、版本记录和最近一次提交的 root hash 在同一个批量写入里写入数据库，
+/// 写入失败时数据库里不会留下只写了一半的 trie（自定义的数据库需要实现 begin_batch 等批量写入的方法）
+fn commit_with<K, V, T>(
+    trie: &mut T,
+    version: Option<(u64, Option<&[u8]>)>,
+) -> Result<Option<HashValue>>
 where
     K: AsRef<[u8]>,
     V: Serialize + DeserializeOwned,
+    T: Trie<K, V> + ?Sized,
 {
-    match proof_db.get(&root_hash)? {
+    // 获得根节点
+    let root_node = trie.take_root_node();
+    // 压缩根节点，节点先被压缩到暂存区里
+    let mut staging = MemoryDatabase::new();
+    let root_node = root_node.collapse::<T::Hasher>(&mut staging)?;
+    let root_hash = match root_node {
+        TrieNodeLink::HashValue(hash_value) => Some(hash_value),
+        TrieNodeLink::Empty => None,
+        // 压缩以后的 trie, 要么是Empty，要么是HashValue，不可能到这里
+        _ => unreachable!(),
+    };
+
+    let result = write_in_batch(trie.db_mut(), |db| {
+        // 将新节点写入数据库，并更新引用计数
+        prune::write_nodes(db, &staging)?;
+        // 固定新的 root hash，在被 prune 或者 unpin 之前，它引用的节点都不会被删除
+        if let Some(root_hash) = root_hash {
+            prune::pin(db, &root_hash)?;
+        }
+        // 记录版本
+        if let Some((number, label)) = version {
+            version::add_version(
+                db,
+                &Version {
+                    number,
+                    label: label.map(|label| label.to_vec()),
+                    root_hash,
+                },
+            )?;
+        }
+        // 记录最近一次提交的 root hash，重新打开数据库时可以从这里继续
+        db.insert(LATEST_ROOT_KEY, bincode::serialize(&root_hash)?)
+    });
+    // 写入失败时，从暂存区里恢复没有提交的根节点
+    if let Err(e) = result {
+        trie.set_root_node(root_node.expand(&staging)?);
+        return Err(e);
+    }
+
+    // 重新设置根节点
+    trie.set_root_node(root_node);
+    // 设置 dirty 标志
+    trie.set_dirty(false);
+    Ok(root_hash)
+}
+
+/// 读取 root_node 里 key 的路径上的节点，读取失败时把 root_node 放回 trie 里再返回错误
+fn load_path<K, V, T>(
+    trie: &mut T,
+    mut root_node: TrieNodeLink,
+    key_nb: &[u8],
+) -> Result<TrieNodeLink>
+where
+    K: AsRef<[u8]>,
+    V: Serialize + DeserializeOwned,
+    T: Trie<K, V> + ?Sized,
+{
+    match root_node.load_path(trie.db_ref(), key_nb) {
+        Ok(()) => Ok(root_node),
+        Err(e) => {
+            trie.set_root_node(root_node);
+            Err(e)
+        }
+    }
+}
+
+/// 从数据库里读取最近一次提交的 root hash
+pub(crate) fn load_latest_root(db: &impl Database) -> Result<Option<HashValue>> {
+    match db.get(&LATEST_ROOT_KEY)? {
+        Some(bin_root) => Ok(bincode::deserialize(&bin_root)?),
+        None => Ok(None),
+    }
+}
+
+/// 将 key 的路径上的节点从 db 收集到 proof_db 里，返回 key 是否存在
+fn collect_proof(
+    db: &impl Database,
+    proof_db: &mut impl Database,
+    root_hash: &HashValue,
+    key: &[u8],
+) -> Result<bool> {
+    // 从数据库里获得根节点的二进制数据
+    match db.get(root_hash)? {
         Some(bin_node) => {
             // 反序列化根节点
-            let trie_node: TrieNode = bincode::deserialize(&bin_node)?;
+            let trie_node = TrieNode::decode(&bin_node)?;
             // 将 key 转换为 nibble 形式
-            let key_nb = util::convert_bytes_to_nibbles(key.as_ref());
-            // 从根节点里获得 key 对应的 value
-            let bin_value_opt = trie_node.get_value(proof_db, &key_nb)?;
-            match bin_value_opt {
-                Some(bin_value) => Ok(Some(bincode::deserialize(&bin_value)?)),
-                None => Ok(None),
-            }
+            let key_nb = util::convert_bytes_to_nibbles(key);
+            // 将根节点插入到 proof_db 里
+            proof_db.insert(*root_hash, bin_node)?;
+            // 通过查找key,将沿途路径上的节点收集到 proof_db 里
+            trie_node.get_proof(db, proof_db, &key_nb)
         }
-        None => Ok(None),
+        // 数据库里没有根节点，root hash 从未被提交过或者已经被删除
+        None => Err(TrieError::UnknownRoot),
+    }
+}
+
+/// 将所有 key 的路径上的节点从 db 收集到同一个 proof 里，重复的节点会被忽略
+/// keys 为空时也检查根节点是否存在，与其他获得 proof 的方法一样返回 TrieError::UnknownRoot
+fn collect_multi_proof<K: AsRef<[u8]>>(
+    db: &impl Database,
+    root_hash: &HashValue,
+    keys: &[K],
+) -> Result<Proof> {
+    if !db.exists(root_hash)? {
+        return Err(TrieError::UnknownRoot);
+    }
+    let mut proof = Proof::new();
+    for key in keys.iter() {
+        collect_proof(db, &mut proof, root_hash, key.as_ref())?;
+    }
+    Ok(proof)
+}
+
+/// 将前缀对应子树路径上和子树里的节点从 db 收集到一个 MemoryDatabase 里
+fn collect_prefix_proof(
+    db: &impl Database,
+    root_hash: &HashValue,
+    prefix: &[u8],
+) -> Result<MemoryDatabase> {
+    // 创建一个 MemoryDatabase
+    let mut proof_db = MemoryDatabase::new();
+    // 如果数据库里没有根节点，那么返回 TrieError::UnknownRoot
+    if !db.exists(root_hash)? {
+        return Err(TrieError::UnknownRoot);
+    }
+    // 将 prefix 转换为 nibble 形式
+    let prefix_nb = util::convert_bytes_to_nibbles(prefix);
+    // 从根节点开始，将路径上和子树里的节点收集到 proof_db 里
+    TrieNodeLink::HashValue(*root_hash).get_prefix_proof(db, &mut proof_db, &prefix_nb)?;
+    Ok(proof_db)
+}
+
+/// 验证紧凑格式的 proof, 返回值与 verify_proof 相同
+pub fn verify_compact_proof<H, K, V>(
+    root_hash: &HashValue,
+    proof: &Proof,
+    key: &K,
+) -> Result<Verified<V>>
+where
+    H: TrieHasher,
+    K: AsRef<[u8]>,
+    V: Serialize + DeserializeOwned,
+{
+    verify_proof::<H, K, V>(root_hash, proof, key)
+}
+
+/// 验证多个 key 共享的 proof, 按 keys 的顺序返回每个 key 的验证结果
+/// 只要有一个 key 的路径不完整，就返回 TrieError::InvalidProof
+pub fn verify_multi_proof<H, K, V>(
+    root_hash: &HashValue,
+    proof: &Proof,
+    keys: &[K],
+) -> Result<Vec<Verified<V>>>
+where
+    H: TrieHasher,
+    K: AsRef<[u8]>,
+    V: Serialize + DeserializeOwned,
+{
+    keys.iter()
+        .map(|key| verify_proof::<H, K, V>(root_hash, proof, key))
+        .collect()
+}
+
+/// 验证前缀 proof, 返回所有以 prefix 开头的 key-value
+/// proof 里的节点会被展开成一棵部分 trie，每个节点的 hash 都会被校验，
+/// 如果有节点被篡改，或者前缀对应的子树不完整，那么返回 TrieError::InvalidProof
+pub fn verify_prefix_proof<H, V>(
+    root_hash: &HashValue,
+    proof_db: &impl Database,
+    prefix: &[u8],
+) -> Result<Vec<(Vec<u8>, V)>>
+where
+    H: TrieHasher,
+    V: Serialize + DeserializeOwned,
+{
+    // 使用校验数据库读取 proof 里的节点
+    let proof_db = VerifyingDatabase::<_, H>::new(proof_db);
+    // 用 proof 里的节点重建部分 trie，不在 proof 里的节点仍然是 HashValue
+    let root_node = TrieNodeLink::HashValue(*root_hash).expand(&proof_db)?;
+    // 将 prefix 转换为 nibble 形式
+    let prefix_nb = util::convert_bytes_to_nibbles(prefix);
+    // 在一个空的数据库上遍历重建的 trie, 遍历时遇到 HashValue 说明 proof 缺少了节点
+    let empty_db = MemoryDatabase::new();
+    TrieIterator::with_prefix(&empty_db, root_node, &prefix_nb)
+        .and_then(|iter| iter.collect())
+        .map_err(|e| match e {
+            TrieError::Database(_) => TrieError::InvalidProof,
+            e => e,
+        })
+}
+
+/// 验证范围 proof, 返回 last_key 之后、end 之前是否可能还有数据
+/// entries 必须恰好是 root hash 对应的 trie 里 key 在 [start, last_key] 范围内的全部数据，
+/// 验证时先用边界路径上的节点重建部分 trie，删除范围内的数据后重新插入 entries，
+/// 再重新计算 root hash，只要有数据被遗漏、增加或者篡改，root hash 就会不一致，返回 TrieError::InvalidProof
+pub fn verify_range_proof<H, V>(
+    root_hash: &HashValue,
+    start: &[u8],
+    end: &[u8],
+    range_proof: &RangeProof<V>,
+) -> Result<bool>
+where
+    H: TrieHasher,
+    V: Serialize + DeserializeOwned,
+{
+    let RangeProof {
+        entries,
+        last_key,
+        proof,
+    } = range_proof;
+    // 右边界必须在 [start, end] 范围内，如果右边界不是 end，那么它必须是最后一条数据的 key
+    let last_key = last_key.as_slice();
+    if start > last_key || last_key > end {
+        return Err(TrieError::InvalidProof);
+    }
+    if last_key != end && entries.last().map(|(key, _)| key.as_slice()) != Some(last_key) {
+        return Err(TrieError::InvalidProof);
+    }
+    // entries 必须按 key 严格递增，并且都在 [start, last_key] 范围内
+    let mut prev_key: Option<&[u8]> = None;
+    for (key, _) in entries.iter() {
+        let key = key.as_slice();
+        if key < start || key > last_key || prev_key.is_some_and(|prev| prev >= key) {
+            return Err(TrieError::InvalidProof);
+        }
+        prev_key = Some(key);
+    }
+
+    // 使用校验数据库读取 proof 里的节点，用边界路径上的节点重建部分 trie
+    let proof_db = VerifyingDatabase::<_, H>::new(proof);
+    let root_node = TrieNodeLink::HashValue(*root_hash).expand(&proof_db)?;
+    // 删除部分 trie 里范围内的数据
+    let start_nb = util::convert_bytes_to_nibbles(start);
+    let last_nb = util::convert_bytes_to_nibbles(last_key);
+    let mut root_node = range::prune(root_node, &[], &start_nb, &last_nb)?;
+    // 重新插入 entries，范围内的节点都已经被删除，插入时不会读取数据库
+    let mut db = MemoryDatabase::new();
+    for (key, value) in entries.iter() {
+        let key_nb = util::convert_bytes_to_nibbles(key);
+        root_node = root_node
+            .insert(&mut db, &key_nb, bincode::serialize(value)?)
+            .map_err(|e| match e {
+                TrieError::Database(_) => TrieError::InvalidProof,
+                e => e,
+            })?;
+    }
+    // 重新计算 root hash
+    match root_node.collapse::<H>(&mut db)? {
+        TrieNodeLink::HashValue(hash_value) if hash_value == *root_hash => Ok(last_key < end),
+        _ => Err(TrieError::InvalidProof),
+    }
+}
+
+/// proof 的验证结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verified<V> {
+    /// key 存在，并且 value 已经通过验证
+    Present(V),
+    /// proof 证明了 key 在 root hash 对应的 trie 里不存在
+    ProvablyAbsent,
+}
+
+impl<V> Verified<V> {
+    /// 转换为 Option, 存在时返回 Some(value)
+    pub fn into_option(self) -> Option<V> {
+        match self {
+            Verified::Present(value) => Some(value),
+            Verified::ProvablyAbsent => None,
+        }
+    }
+}
+
+/// 验证 proof, 返回 key 对应的 value
+/// 如果 key 存在，那么返回 Verified::Present(value)
+/// 如果 proof 证明了 key 不存在，那么返回 Verified::ProvablyAbsent
+/// 如果 proof 缺少了路径上的节点，无法得出结论，那么返回 TrieError::InvalidProof
+/// 如果 proof 里的节点数据与其 hash 不一致，说明 proof 被篡改了，同样返回 TrieError::InvalidProof
+/// H 必须与生成 proof 的 trie 使用的 hash 函数相同
+pub fn verify_proof<H, K, V>(
+    root_hash: &HashValue,
+    proof_db: &impl Database,
+    key: &K,
+) -> Result<Verified<V>>
+where
+    H: TrieHasher,
+    K: AsRef<[u8]>,
+    V: Serialize + DeserializeOwned,
+{
+    // 使用校验数据库读取 proof 里的节点，每个节点的 hash 都会被重新计算
+    let proof_db = &VerifyingDatabase::<_, H>::new(proof_db);
+    // 缺少根节点的 proof 既不能证明 key 存在，也不能证明 key 不存在
+    let bin_node = proof_db.get(root_hash)?.ok_or(TrieError::InvalidProof)?;
+    // 反序列化根节点
+    let trie_node = TrieNode::decode(&bin_node)?;
+    // 将 key 转换为 nibble 形式
+    let key_nb = util::convert_bytes_to_nibbles(key.as_ref());
+    // 从根节点里获得 key 对应的 value
+    // 查找会在 key 的路径与 trie 分叉的地方停下，所以 None 表示 key 不存在，
+    // 而路径上的节点在 proof 里找不到时，说明 proof 是不完整的
+    let bin_value_opt = trie_node
+        .get_value(proof_db, &key_nb)
+        .map_err(|e| match e {
+            TrieError::Database(_) => TrieError::InvalidProof,
+            e => e,
+        })?;
+    match bin_value_opt {
+        Some(bin_value) => Ok(Verified::Present(bincode::deserialize(&bin_value)?)),
+        None => Ok(Verified::ProvablyAbsent),
     }
 }
 
@@ -222,12 +728,1171 @@ mod tests {
         assert!(value.is_some());
     }
 
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn rocksdb_open_works() {
+        use super::rocksdb_trie::RocksdbTrie;
+        let db_path: std::path::PathBuf = "/tmp/tinympt_open_db".into();
+        let _ = std::fs::remove_dir_all(&db_path);
+
+        // 提交两次，然后关闭数据库
+        let (root_hash1, root_hash2) = {
+            let mut trie = RocksdbTrie::<&'static str, String>::new(db_path.clone());
+            trie.insert("0000", "value01".to_string()).unwrap();
+            let root_hash1 = trie.commit().unwrap().unwrap();
+            trie.insert("00001111", "value02".to_string()).unwrap();
+            let root_hash2 = trie.commit().unwrap().unwrap();
+            (root_hash1, root_hash2)
+        };
+
+        // 重新打开数据库，从最近一次提交的 root hash 继续
+        {
+            let trie = RocksdbTrie::<&'static str, String>::open(db_path.clone()).unwrap();
+            assert_eq!(trie.latest_root().unwrap(), Some(root_hash2));
+            assert_eq!(trie.get_value(&"00001111").unwrap().unwrap(), "value02");
+        }
+
+        // 从历史 root hash 开始
+        {
+            let trie =
+                RocksdbTrie::<&'static str, String>::open_at(db_path.clone(), root_hash1).unwrap();
+            assert!(trie.get_value(&"00001111").unwrap().is_none());
+            assert_eq!(trie.get_value(&"0000").unwrap().unwrap(), "value01");
+        }
+
+        // 数据库里没有的 root hash
+        let result = RocksdbTrie::<&'static str, String>::open_at(db_path, [1; 32]);
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+    }
+
+    #[test]
+    fn memory_latest_root_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        latest_root_works(&mut trie);
+    }
+
+    fn latest_root_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        // 还没有提交过
+        assert_eq!(trie.latest_root().unwrap(), None);
+
+        // 每次提交后，数据库里记录的都是最近一次提交的 root hash
+        trie.insert("0000", "value01".to_string()).unwrap();
+        let root_hash1 = trie.commit().unwrap().unwrap();
+        assert_eq!(trie.latest_root().unwrap(), Some(root_hash1));
+
+        trie.insert("00001111", "value02".to_string()).unwrap();
+        let root_hash2 = trie.commit().unwrap().unwrap();
+        assert_eq!(trie.latest_root().unwrap(), Some(root_hash2));
+
+        // revert 不是提交，最近一次提交的 root hash 保持不变
+        trie.revert(root_hash1).unwrap();
+        assert_eq!(trie.latest_root().unwrap(), Some(root_hash2));
+    }
+
+    #[test]
+    fn memory_version_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        version_works(&mut trie);
+    }
+
+    fn version_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        // 没有任何版本
+        assert!(trie.latest_version().unwrap().is_none());
+
+        // 提交空的 trie 作为版本 0
+        assert!(trie.commit_as(0, None).unwrap().is_none());
+        // 按区块高度提交，并以区块 hash 作为标签
+        trie.insert("0000", "value01".to_string()).unwrap();
+        let root_hash1 = trie.commit_as(1, Some(b"block01")).unwrap().unwrap();
+        trie.insert("00001111", "value02".to_string()).unwrap();
+        let root_hash2 = trie.commit_as(3, Some(b"block03")).unwrap().unwrap();
+
+        // 版本号必须单调递增
+        let result = trie.commit_as(3, None);
+        assert!(matches!(result, Err(TrieError::InvalidVersion)));
+        let result = trie.commit_as(2, None);
+        assert!(matches!(result, Err(TrieError::InvalidVersion)));
+
+        // 列出所有的版本
+        let versions = trie.versions().unwrap();
+        let numbers: Vec<_> = versions.iter().map(|version| version.number).collect();
+        assert_eq!(numbers, vec![0, 1, 3]);
+        assert_eq!(versions[0].root_hash, None);
+        assert_eq!(versions[1].root_hash, Some(root_hash1));
+
+        let latest = trie.latest_version().unwrap().unwrap();
+        assert_eq!(latest.number, 3);
+        assert_eq!(latest.root_hash, Some(root_hash2));
+
+        // 切换到版本 1
+        trie.checkout(1).unwrap();
+        assert!(trie.get_value(&"00001111").unwrap().is_none());
+        assert_eq!(trie.get_value(&"0000").unwrap().unwrap(), "value01");
+
+        // 通过标签找到版本 3，然后切换过去
+        let version = trie.version_by_label(b"block03").unwrap().unwrap();
+        assert_eq!(version.number, 3);
+        trie.checkout(version.number).unwrap();
+        assert_eq!(trie.get_value(&"00001111").unwrap().unwrap(), "value02");
+
+        // 切换到版本 0，trie 为空
+        trie.checkout(0).unwrap();
+        assert!(trie.get_value(&"0000").unwrap().is_none());
+
+        // 不存在的版本
+        assert!(matches!(trie.checkout(2), Err(TrieError::InvalidVersion)));
+        assert!(trie.version_by_label(b"block02").unwrap().is_none());
+    }
+
+    #[test]
+    fn memory_prune_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        prune_works(&mut trie);
+    }
+
+    fn prune_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+            ("pellet02_state01_key01", "value04".to_string()),
+        ];
+
+        // 每次提交都修改一部分数据，相邻的 root hash 之间会共享没有修改的节点
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash1 = trie.commit_as(1, None).unwrap().unwrap();
+        trie.insert(data[0].0, "value05".to_string()).unwrap();
+        let root_hash2 = trie.commit_as(2, None).unwrap().unwrap();
+        trie.remove(&data[3].0).unwrap();
+        let root_hash3 = trie.commit_as(3, None).unwrap().unwrap();
+
+        // 只保留最近两次提交，root hash 1 独有的节点被删除
+        assert!(trie.prune(2).unwrap() > 0);
+        let numbers: Vec<_> = trie.versions().unwrap().iter().map(|v| v.number).collect();
+        assert_eq!(numbers, vec![2, 3]);
+        trie.revert(root_hash1).unwrap();
+        assert!(trie.get_value(&data[0].0).is_err());
+
+        // 保留的 root hash 仍然是完整的，包括与 root hash 1 共享的节点
+        for (root_hash, len) in [(root_hash2, 4), (root_hash3, 3)] {
+            let entries: Vec<_> = trie.iter(Some(root_hash)).collect::<Result<_>>().unwrap();
+            assert_eq!(entries.len(), len);
+        }
+
+        // 再次提交同一个 root hash 不会重复固定，最近一次提交的 root hash 不能被取消固定
+        trie.revert(root_hash3).unwrap();
+        assert_eq!(trie.commit().unwrap().unwrap(), root_hash3);
+        assert_eq!(
+            prune::load_pinned_roots(trie.db_ref()).unwrap(),
+            vec![root_hash2, root_hash3]
+        );
+        assert!(matches!(
+            trie.unpin(&root_hash3),
+            Err(TrieError::LatestRoot)
+        ));
+        assert_eq!(trie.get_value(&data[1].0).unwrap().unwrap(), data[1].1);
+
+        // 没有被固定的 root hash
+        assert!(matches!(
+            trie.unpin(&root_hash1),
+            Err(TrieError::InvalidHashValue)
+        ));
+
+        // 取消固定 root hash 2，只保留 root hash 3
+        assert!(trie.unpin(&root_hash2).unwrap() > 0);
+        assert!(trie.iter(Some(root_hash2)).any(|item| item.is_err()));
+        let entries: Vec<_> = trie.iter(Some(root_hash3)).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(trie.latest_version().unwrap().unwrap().number, 3);
+
+        // 被删除的节点可以重新写入
+        trie.insert(data[3].0, data[3].1.clone()).unwrap();
+        trie.insert(data[0].0, data[0].1.clone()).unwrap();
+        assert_eq!(trie.commit().unwrap().unwrap(), root_hash1);
+        let entries: Vec<_> = trie.iter(Some(root_hash1)).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 4);
+
+        // 重复提交同一个 root hash, prune 按不同的 root hash 计数
+        for _ in 0..3 {
+            assert_eq!(trie.commit().unwrap().unwrap(), root_hash1);
+        }
+        trie.revert(root_hash3).unwrap();
+        trie.commit().unwrap();
+        trie.revert(root_hash1).unwrap();
+        trie.commit().unwrap();
+        assert_eq!(trie.prune(2).unwrap(), 0);
+        let entries: Vec<_> = trie.iter(Some(root_hash3)).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 3);
+
+        // prune(0) 也会保留最近一次提交的 root hash
+        assert!(trie.prune(0).unwrap() > 0);
+        assert!(prune::load_pinned_roots(trie.db_ref())
+            .unwrap()
+            .iter()
+            .all(|root_hash| *root_hash == root_hash1));
+        assert!(trie.iter(Some(root_hash3)).any(|item| item.is_err()));
+        let entries: Vec<_> = trie.iter(Some(root_hash1)).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 4);
+    }
+
+    #[test]
+    fn memory_gc_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        gc_works(&mut trie);
+    }
+
+    /// 统计从 root hash 出发可以访问到的节点数量
+    fn count_nodes(db: &impl Database, root_hash: HashValue) -> usize {
+        let mut hashes = std::collections::HashSet::new();
+        let mut stack = vec![root_hash];
+        while let Some(hash_value) = stack.pop() {
+            if hashes.insert(hash_value) {
+                let bin_node = db.get(&hash_value).unwrap().unwrap();
+                let trie_node = TrieNode::decode(&bin_node).unwrap();
+                stack.extend(trie_node.child_hashes());
+            }
+        }
+        hashes.len()
+    }
+
+    /// 统计数据库里的节点数量
+    fn count_db_nodes<H: TrieHasher>(db: &impl Database) -> usize {
+        db.keys()
+            .unwrap()
+            .into_iter()
+            .filter(|key| H::hash(&db.get(key).unwrap().unwrap()) == *key)
+            .count()
+    }
+
+    fn gc_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+            ("pellet02_state01_key01", "value04".to_string()),
+        ];
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash1 = trie.commit_as(1, None).unwrap().unwrap();
+        trie.insert(data[0].0, "value05".to_string()).unwrap();
+        let root_hash2 = trie.commit_as(2, None).unwrap().unwrap();
+
+        // 模拟 commit 中途崩溃时留下的节点
+        let orphan = TrieNode::Node(node::Node::new(vec![1, 2, 3], vec![4, 5, 6])).encode();
+        trie.db_mut()
+            .insert(T::Hasher::hash(&orphan), orphan)
+            .unwrap();
+
+        // 只保留 root hash 2
+        let stats = gc::<T::Hasher>(trie.db_mut(), &[root_hash2]).unwrap();
+        assert!(stats.nodes > 1 && stats.bytes > 0);
+        assert_eq!(
+            count_db_nodes::<T::Hasher>(trie.db_ref()),
+            count_nodes(trie.db_ref(), root_hash2)
+        );
+        assert!(trie.iter(Some(root_hash1)).any(|item| item.is_err()));
+        let entries: Vec<_> = trie.iter(Some(root_hash2)).collect::<Result<_>>().unwrap();
+        assert_eq!(entries.len(), 4);
+        let numbers: Vec<_> = trie.versions().unwrap().iter().map(|v| v.number).collect();
+        assert_eq!(numbers, vec![2]);
+
+        // 再次运行不会删除任何数据
+        assert_eq!(
+            gc::<T::Hasher>(trie.db_mut(), &[root_hash2]).unwrap(),
+            GcStats::default()
+        );
+
+        // gc 之后引用计数仍然正确，prune 只留下最新的 root hash 引用的节点
+        trie.remove(&data[3].0).unwrap();
+        let root_hash3 = trie.commit().unwrap().unwrap();
+        assert!(trie.prune(1).unwrap() > 0);
+        assert_eq!(
+            count_db_nodes::<T::Hasher>(trie.db_ref()),
+            count_nodes(trie.db_ref(), root_hash3)
+        );
+
+        // live_roots 为空时，最近一次提交的 root hash 引用的节点也不会被删除
+        assert_eq!(
+            gc::<T::Hasher>(trie.db_mut(), &[]).unwrap(),
+            GcStats::default()
+        );
+        assert_eq!(trie.latest_root().unwrap(), Some(root_hash3));
+        assert_eq!(trie.iter(Some(root_hash3)).count(), 3);
+
+        // 缺少节点时不删除任何数据
+        let result = gc::<T::Hasher>(trie.db_mut(), &[root_hash1]);
+        assert!(matches!(result, Err(TrieError::Database(_))));
+        assert_eq!(
+            count_db_nodes::<T::Hasher>(trie.db_ref()),
+            count_nodes(trie.db_ref(), root_hash3)
+        );
+    }
+
+    #[test]
+    fn memory_batch_works() {
+        let mut db = MemoryDatabase::new();
+        db.insert([1; 32], vec![1]).unwrap();
+
+        // 批量写入里的修改在写入之前就可以读取
+        db.begin_batch().unwrap();
+        db.insert([2; 32], vec![2]).unwrap();
+        db.remove(&[1; 32]).unwrap();
+        assert_eq!(db.get(&[2; 32]).unwrap(), Some(vec![2]));
+        assert!(!db.exists(&[1; 32]).unwrap());
+        assert_eq!(db.keys().unwrap(), vec![[2; 32]]);
+        // 丢弃之后数据库没有变化
+        db.discard_batch();
+        assert_eq!(db.get(&[1; 32]).unwrap(), Some(vec![1]));
+        assert!(db.get(&[2; 32]).unwrap().is_none());
+
+        // 写入之后修改都存在，删除也在同一个批量写入里完成
+        db.begin_batch().unwrap();
+        db.insert([2; 32], vec![2]).unwrap();
+        db.remove(&[1; 32]).unwrap();
+        assert_eq!(db.into_iter().count(), 1);
+        db.write_batch().unwrap();
+        assert!(db.get(&[1; 32]).unwrap().is_none());
+        assert_eq!(db.get(&[2; 32]).unwrap(), Some(vec![2]));
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn rocksdb_batch_works() {
+        use crate::database::RocksdbDatabase;
+        let db_path: std::path::PathBuf = "/tmp/tinympt_batch_db".into();
+        let _ = std::fs::remove_dir_all(&db_path);
+        let mut db = RocksdbDatabase::new(db_path);
+
+        // 批量写入里的修改在写入之前就可以读取
+        db.begin_batch().unwrap();
+        db.insert([1; 32], vec![1]).unwrap();
+        db.insert([2; 32], vec![2]).unwrap();
+        assert_eq!(db.get(&[1; 32]).unwrap(), Some(vec![1]));
+        // 丢弃之后修改都不存在
+        db.discard_batch();
+        assert!(db.get(&[1; 32]).unwrap().is_none());
+
+        // 写入之后修改都存在，删除也在同一个批量写入里完成
+        db.begin_batch().unwrap();
+        db.insert([1; 32], vec![1]).unwrap();
+        db.insert([2; 32], vec![2]).unwrap();
+        db.remove(&[2; 32]).unwrap();
+        assert_eq!(db.keys().unwrap(), vec![[1; 32]]);
+        db.write_batch().unwrap();
+        assert_eq!(db.get(&[1; 32]).unwrap(), Some(vec![1]));
+        assert!(db.get(&[2; 32]).unwrap().is_none());
+        // 被删除的和从来没有写入的 key 都不存在
+        assert!(db.exists(&[1; 32]).unwrap());
+        assert!(!db.exists(&[2; 32]).unwrap());
+        assert!(!db.exists(&[3; 32]).unwrap());
+    }
+
+    #[test]
+    fn memory_snapshot_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        snapshot_works(&mut trie);
+    }
+
+    #[cfg(feature = "rocksdb")]
+    #[test]
+    fn rocksdb_snapshot_works() {
+        use super::rocksdb_trie::RocksdbTrie;
+        let db_path: std::path::PathBuf = "/tmp/tinympt_snapshot_db".into();
+        let _ = std::fs::remove_dir_all(&db_path);
+        let mut trie = RocksdbTrie::<&'static str, String>::new(db_path);
+        snapshot_works(&mut trie);
+    }
+
+    fn snapshot_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+        T::Database: Clone + Send + Sync,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01"),
+            ("pellet01_state01_key02", "value02"),
+            ("pellet01_state02_key01", "value03"),
+            ("pellet01_state02_key02", "value04"),
+        ];
+        for (key, value) in data {
+            trie.insert(key, value.to_string()).unwrap();
+        }
+
+        // 创建快照时会先提交
+        let snapshot = trie.snapshot().unwrap();
+        assert!(!trie.dirty());
+        let root_hash = snapshot.root_hash().unwrap();
+        assert_eq!(trie.latest_root().unwrap(), Some(root_hash));
+
+        // trie 继续写入和提交，不会影响快照
+        trie.insert("pellet02_state01_key01", "value05".to_string())
+            .unwrap();
+        trie.remove(&"pellet01_state01_key01").unwrap();
+        let root_hash2 = trie.commit().unwrap().unwrap();
+        assert_eq!(
+            snapshot.get_value(&"pellet01_state01_key01").unwrap(),
+            Some("value01".to_string())
+        );
+        assert!(snapshot
+            .get_value(&"pellet02_state01_key01")
+            .unwrap()
+            .is_none());
+        assert_eq!(snapshot.iter().count(), 4);
+        assert_eq!(
+            snapshot.iter_prefix(b"pellet01_state02").unwrap().count(),
+            2
+        );
+
+        // 多个线程同时使用快照的克隆生成 proof
+        std::thread::scope(|scope| {
+            for (key, value) in data {
+                let snapshot = snapshot.clone();
+                scope.spawn(move || {
+                    let (exists, proof_db) = snapshot.get_proof(&key).unwrap();
+                    assert!(exists);
+                    let verified =
+                        verify_proof::<T::Hasher, _, String>(&root_hash, &proof_db, &key).unwrap();
+                    assert_eq!(verified.into_option(), Some(value.to_string()));
+                    let (_, proof) = snapshot.get_compact_proof(&key).unwrap();
+                    let verified =
+                        verify_compact_proof::<T::Hasher, _, String>(&root_hash, &proof, &key);
+                    assert_eq!(verified.unwrap().into_option(), Some(value.to_string()));
+                });
+            }
+        });
+
+        // 范围 proof 也可以从快照生成
+        let range_proof = snapshot
+            .get_range_proof(b"pellet01_state01", b"pellet01_state02", 10)
+            .unwrap();
+        assert_eq!(range_proof.entries.len(), 2);
+        let has_more = verify_range_proof::<T::Hasher, _>(
+            &root_hash,
+            b"pellet01_state01",
+            b"pellet01_state02",
+            &range_proof,
+        );
+        assert!(!has_more.unwrap());
+
+        // 新的快照绑定到最新提交的 root hash，at 可以切换到数据库里的其他 root hash
+        let snapshot2 = trie.snapshot().unwrap();
+        assert_eq!(snapshot2.root_hash(), Some(root_hash2));
+        assert!(snapshot2
+            .get_value(&"pellet01_state01_key01")
+            .unwrap()
+            .is_none());
+        let snapshot1 = snapshot2.at(root_hash);
+        assert_eq!(
+            snapshot1.get_value(&"pellet01_state01_key01").unwrap(),
+            Some("value01".to_string())
+        );
+
+        // 数据库里没有的 root hash
+        assert!(matches!(
+            snapshot1.at([1; 32]).get_range_proof(b"a", b"b", 1),
+            Err(TrieError::UnknownRoot)
+        ));
+        assert!(matches!(
+            snapshot1.at([1; 32]).get_multi_proof(&[]),
+            Err(TrieError::UnknownRoot)
+        ));
+
+        // 空的快照里没有数据，也没有可以验证 proof 的 root hash
+        let empty = TrieSnapshot::<_, &str, String>::new(Arc::new(MemoryDatabase::new()), None);
+        let key = "pellet01_state01_key01";
+        assert!(empty.get_value(&key).unwrap().is_none());
+        assert_eq!(empty.iter().count(), 0);
+        assert!(matches!(empty.get_proof(&key), Err(TrieError::UnknownRoot)));
+        assert!(matches!(
+            empty.get_compact_proof(&key),
+            Err(TrieError::UnknownRoot)
+        ));
+        assert!(matches!(
+            empty.get_multi_proof(&[key]),
+            Err(TrieError::UnknownRoot)
+        ));
+        assert!(matches!(
+            empty.get_prefix_proof(b"pellet01"),
+            Err(TrieError::UnknownRoot)
+        ));
+        assert!(matches!(
+            empty.get_range_proof(b"a", b"b", 1),
+            Err(TrieError::UnknownRoot)
+        ));
+    }
+
+    #[test]
+    fn memory_extension_fork_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        let mut expected = MemoryTrie::<&'static str, String>::new();
+        extension_fork_works(&mut trie, &mut expected);
+    }
+
+    /// 插入的 key 与扩展节点的 partial_key 只在最后一个 nibble 分叉
+    fn extension_fork_works<'a, T>(trie: &mut T, expected: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        // "ab1" 和 "ab2" 的 nibble 为 [6, 1, 6, 2, 3, 1] 和 [6, 1, 6, 2, 3, 2]，
+        // 插入之后根节点是 partial_key 为 [6, 1, 6, 2, 3] 的扩展节点。
+        // "abA" 的 nibble 为 [6, 1, 6, 2, 4, 1]，与 partial_key 的共同前缀为 [6, 1, 6, 2]，
+        // partial_key 只剩下一个 nibble，共同前缀需要保留在新的扩展节点里
+        let data = [
+            ("ab1", "value01".to_string()),
+            ("ab2", "value02".to_string()),
+            ("abA", "value03".to_string()),
+        ];
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        for (key, value) in data.iter() {
+            assert_eq!(trie.get_value(key).unwrap().unwrap(), *value);
+        }
+
+        // 按相反的顺序插入时，"ab1" 插入的扩展节点是 [6, 1, 6, 2]，不会在最后一个 nibble 分叉
+        for (key, value) in data.iter().rev() {
+            expected.insert(key, value.clone()).unwrap();
+        }
+        // root hash 与插入顺序无关
+        let root_hash = trie.commit().unwrap().unwrap();
+        assert_eq!(root_hash, expected.commit().unwrap().unwrap());
+
+        // 提交之后从数据库里读取
+        for (key, value) in data.iter() {
+            assert_eq!(trie.get_value(key).unwrap().unwrap(), *value);
+        }
+    }
+
+    #[test]
+    fn memory_remove_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        remove_works(&mut trie);
+    }
+
+    fn remove_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        // 准备数据，这些 key 会让 trie 里同时出现分支节点、扩展节点和叶子节点
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+            ("pellet02_state01_key01", "value04".to_string()),
+        ];
+        let extra = [
+            ("pellet01_state01_key03", "value05".to_string()),
+            ("pellet01_state01", "value06".to_string()),
+            ("pellet03", "value07".to_string()),
+        ];
+
+        // 插入数据，并获得 root hash
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash1 = trie.commit().unwrap().unwrap();
+
+        // 插入额外的数据并提交
+        for (key, value) in extra.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash2 = trie.commit().unwrap().unwrap();
+        assert_ne!(root_hash1, root_hash2);
+
+        // 删除不存在的 key，trie 保持不变
+        assert!(trie.remove(&"pellet04").unwrap().is_none());
+        assert_eq!(trie.commit().unwrap().unwrap(), root_hash2);
+
+        // 删除额外的数据，检查返回的 value 是否正确
+        for (key, value) in extra.iter() {
+            assert_eq!(trie.remove(key).unwrap().unwrap(), *value);
+            assert!(trie.get_value(key).unwrap().is_none());
+        }
+        // 删除之后的 root hash 与从未插入额外数据时相同
+        assert_eq!(trie.commit().unwrap().unwrap(), root_hash1);
+
+        // 删除所有数据，trie 变为空
+        for (key, value) in data.iter() {
+            assert_eq!(trie.remove(key).unwrap().unwrap(), *value);
+        }
+        assert!(trie.commit().unwrap().is_none());
+    }
+
+    /// 读取数据可能失败的数据库，fail 为 true 时所有的读取都返回错误
+    #[derive(Default)]
+    struct FailingDatabase {
+        db: MemoryDatabase,
+        fail: bool,
+    }
+
+    impl Database for FailingDatabase {
+        fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+            if self.fail {
+                return Err(TrieError::Database("read failure".to_string()));
+            }
+            self.db.get(key)
+        }
+
+        fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Result<()> {
+            self.db.insert(key, value)
+        }
+
+        fn exists(&self, key: &HashValue) -> Result<bool> {
+            self.db.exists(key)
+        }
+
+        fn remove(&mut self, key: &HashValue) -> Result<()> {
+            self.db.remove(key)
+        }
+
+        fn keys(&self) -> Result<Vec<HashValue>> {
+            self.db.keys()
+        }
+    }
+
+    #[test]
+    fn remove_keeps_root_on_error_works() {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet02_state01_key01", "value03".to_string()),
+        ];
+        let db = FailingDatabase::default();
+        let mut trie = MemoryTrie::<&'static str, String, Blake2bHasher, _>::with_database(db);
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        trie.commit().unwrap();
+        // 没有提交的数据
+        trie.insert("pellet03", "value04".to_string()).unwrap();
+
+        // 删除和插入时读取数据库失败，trie 保持原样
+        trie.db_mut().fail = true;
+        let result = trie.remove(&"pellet01_state01_key01");
+        assert!(matches!(result, Err(TrieError::Database(_))));
+        let result = trie.insert("pellet01_state01_key03", "value05".to_string());
+        assert!(matches!(result, Err(TrieError::Database(_))));
+        trie.db_mut().fail = false;
+
+        // 已经提交和没有提交的数据都还在
+        for (key, value) in data.iter() {
+            assert_eq!(trie.get_value(key).unwrap().unwrap(), *value);
+        }
+        assert_eq!(trie.get_value(&"pellet03").unwrap().unwrap(), "value04");
+        assert!(trie.get_value(&"pellet01_state01_key03").unwrap().is_none());
+
+        // 提交之后的 root hash 与没有出错时相同
+        let mut expected = MemoryTrie::<&'static str, String>::new();
+        for (key, value) in data.iter() {
+            expected.insert(key, value.clone()).unwrap();
+        }
+        expected.insert("pellet03", "value04".to_string()).unwrap();
+        assert_eq!(trie.commit().unwrap(), expected.commit().unwrap());
+    }
+
+    #[test]
+    fn memory_iter_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        iter_works(&mut trie);
+    }
+
+    fn iter_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        // 准备数据，插入顺序是乱序的
+        let data = [
+            ("pellet02_state01_key01", "value04".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state01", "value01".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+        ];
+        // 期望按 key 的字典序返回
+        let mut expected: Vec<(Vec<u8>, String)> = data
+            .iter()
+            .map(|(key, value)| (key.as_bytes().to_vec(), value.clone()))
+            .collect();
+        expected.sort();
+
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        // 每个 key 都能查到
+        for (key, value) in data.iter() {
+            assert_eq!(trie.get_value(key).unwrap().unwrap(), *value);
+        }
+        // 遍历未提交的 trie
+        let items: Vec<_> = trie.iter(None).collect::<Result<_>>().unwrap();
+        assert_eq!(items, expected);
+
+        // 遍历已提交的 trie
+        let root_hash1 = trie.commit().unwrap().unwrap();
+        let items: Vec<_> = trie.iter(None).collect::<Result<_>>().unwrap();
+        assert_eq!(items, expected);
+
+        // 插入新数据后，仍然可以遍历旧的 root hash
+        trie.insert("pellet03", "value05".to_string()).unwrap();
+        trie.commit().unwrap();
+        let items: Vec<_> = trie.iter(Some(root_hash1)).collect::<Result<_>>().unwrap();
+        assert_eq!(items, expected);
+        assert_eq!(trie.iter(None).count(), expected.len() + 1);
+    }
+
+    #[test]
+    fn memory_iter_prefix_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        iter_prefix_works(&mut trie);
+    }
+
+    fn iter_prefix_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+            ("pellet01_state02_key02", "value04".to_string()),
+            ("pellet02_state01_key01", "value05".to_string()),
+        ];
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        trie.commit().unwrap();
+
+        // 获得以 prefix 开头的所有 key
+        let keys_with_prefix = |trie: &T, prefix: &str| -> Vec<String> {
+            trie.iter_prefix(prefix.as_bytes())
+                .unwrap()
+                .map(|item| String::from_utf8(item.unwrap().0).unwrap())
+                .collect()
+        };
+
+        // prefix 正好落在分支节点上
+        assert_eq!(
+            keys_with_prefix(trie, "pellet01_state02"),
+            vec!["pellet01_state02_key01", "pellet01_state02_key02"]
+        );
+        // prefix 在扩展节点的 partial_key 中间结束
+        assert_eq!(keys_with_prefix(trie, "pellet01_sta").len(), 4);
+        // prefix 在叶子节点的 rest_of_key 中间结束
+        assert_eq!(
+            keys_with_prefix(trie, "pellet02_st"),
+            vec!["pellet02_state01_key01"]
+        );
+        // prefix 是一个完整的 key
+        assert_eq!(
+            keys_with_prefix(trie, "pellet01_state01_key02"),
+            vec!["pellet01_state01_key02"]
+        );
+        // 没有匹配的 prefix
+        assert!(keys_with_prefix(trie, "pellet03").is_empty());
+        assert!(keys_with_prefix(trie, "pellet01_state02_key03").is_empty());
+        // 空的 prefix 匹配所有数据
+        assert_eq!(keys_with_prefix(trie, "").len(), data.len());
+    }
+
+    #[test]
+    fn memory_compact_proof_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        compact_proof_works(&mut trie);
+    }
+
+    fn compact_proof_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+            ("pellet02_state01_key01", "value04".to_string()),
+        ];
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash = trie.commit().unwrap().unwrap();
+
+        // 获得紧凑格式的 proof, 第一个节点是根节点
+        let (exists, proof) = trie.get_compact_proof(&root_hash, &data[1].0).unwrap();
+        assert!(exists);
+        assert_eq!(T::Hasher::hash(&proof.nodes()[0]), root_hash);
+        // 紧凑格式与 MemoryDatabase 包含相同的节点，但是编码后更小
+        let (_, proof_db) = trie.get_proof(&root_hash, &data[1].0).unwrap();
+        assert!(proof.encode().len() < bincode::serialize(&proof_db).unwrap().len());
+
+        // 编码后再解码，然后验证
+        let proof = Proof::decode::<T::Hasher>(&proof.encode()).unwrap();
+        let value =
+            verify_compact_proof::<T::Hasher, _, String>(&root_hash, &proof, &data[1].0).unwrap();
+        assert_eq!(value, Verified::Present(data[1].1.clone()));
+
+        // 不存在的 key
+        let key = "pellet01_state03";
+        let (exists, proof) = trie.get_compact_proof(&root_hash, &key).unwrap();
+        assert!(!exists);
+        let value = verify_compact_proof::<T::Hasher, _, String>(&root_hash, &proof, &key).unwrap();
+        assert_eq!(value, Verified::ProvablyAbsent);
+
+        // 被截断的 proof 无法解码
+        let bin_proof = proof.encode();
+        let result = Proof::decode::<T::Hasher>(&bin_proof[..bin_proof.len() - 1]);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 数据库里没有的 root hash
+        let result = trie.get_compact_proof(&[1; 32], &key);
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+        let result = trie.get_prefix_proof(&[1; 32], b"pellet01");
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+        let result = trie.get_range_proof(&[1; 32], b"a", b"b", 10);
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+    }
+
+    #[test]
+    fn memory_multi_proof_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        multi_proof_works(&mut trie);
+    }
+
+    fn multi_proof_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            // value 足够长，叶子节点不会内嵌在父节点里
+            ("pellet01_state02_key01", "value03".repeat(4)),
+            ("pellet02_state01_key01", "value04".to_string()),
+        ];
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash = trie.commit().unwrap().unwrap();
+
+        // 同时证明存在的 key 和不存在的 key
+        let keys = [data[0].0, data[1].0, "pellet01_state03", data[3].0];
+        let proof = trie.get_multi_proof(&root_hash, &keys).unwrap();
+
+        // 每个节点只出现一次，节点数量等于所有单个 proof 的节点的并集
+        let mut hashes = std::collections::HashSet::new();
+        for key in keys.iter() {
+            let (_, single) = trie.get_compact_proof(&root_hash, key).unwrap();
+            hashes.extend(single.nodes().iter().map(|node| T::Hasher::hash(node)));
+        }
+        assert_eq!(proof.nodes().len(), hashes.len());
+
+        // 编码后再解码，然后验证
+        let proof = Proof::decode::<T::Hasher>(&proof.encode()).unwrap();
+        let values = verify_multi_proof::<T::Hasher, _, String>(&root_hash, &proof, &keys).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                Verified::Present(data[0].1.clone()),
+                Verified::Present(data[1].1.clone()),
+                Verified::ProvablyAbsent,
+                Verified::Present(data[3].1.clone()),
+            ]
+        );
+
+        // proof 里没有 data[2] 的路径，验证失败
+        let result = verify_multi_proof::<T::Hasher, _, String>(&root_hash, &proof, &[data[2].0]);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 数据库里没有的 root hash，没有 key 时也返回 TrieError::UnknownRoot
+        let result = trie.get_multi_proof(&[1; 32], &keys);
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+        let result = trie.get_multi_proof(&[1; 32], &[]);
+        assert!(matches!(result, Err(TrieError::UnknownRoot)));
+        // 没有 key 时得到空的 proof
+        let proof = trie.get_multi_proof(&root_hash, &[]).unwrap();
+        assert!(proof.nodes().is_empty());
+    }
+
+    #[test]
+    fn memory_range_proof_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        range_proof_works(&mut trie);
+    }
+
+    fn range_proof_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01", "value01".to_string()),
+            ("pellet01_state01", "value02".to_string()),
+            ("pellet01_state01_key01", "value03".to_string()),
+            ("pellet01_state01_key02", "value04".to_string()),
+            ("pellet01_state02_key01", "value05".to_string()),
+            ("pellet02_state01_key01", "value06".to_string()),
+            ("pellet03", "value07".to_string()),
+        ];
+        for (key, value) in data.iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash = trie.commit().unwrap().unwrap();
+
+        // 边界既有存在的 key，也有不存在的 key
+        let bounds = [
+            "",
+            "pellet",
+            "pellet01",
+            "pellet01_state01",
+            "pellet01_state01_key02",
+            "pellet01_state015",
+            "pellet02",
+            "pellet03",
+            "pellet04",
+        ];
+        for start in bounds.iter() {
+            for end in bounds.iter().filter(|end| start <= *end) {
+                let expected: Vec<_> = data
+                    .iter()
+                    .filter(|(key, _)| start <= key && key <= end)
+                    .map(|(key, value)| (key.as_bytes().to_vec(), value.clone()))
+                    .collect();
+                for limit in 1..=3 {
+                    // 按页获取范围内的数据，每一页都从上一页的最后一条数据之后开始
+                    let mut entries = Vec::new();
+                    let mut page_start = start.as_bytes().to_vec();
+                    loop {
+                        let range_proof = trie
+                            .get_range_proof(&root_hash, &page_start, end.as_bytes(), limit)
+                            .unwrap();
+                        let has_more = verify_range_proof::<T::Hasher, _>(
+                            &root_hash,
+                            &page_start,
+                            end.as_bytes(),
+                            &range_proof,
+                        )
+                        .unwrap();
+                        entries.extend(range_proof.entries);
+                        if !has_more {
+                            break;
+                        }
+                        page_start = [range_proof.last_key, vec![0]].concat();
+                    }
+                    assert_eq!(entries, expected);
+                }
+            }
+        }
+
+        let (start, end) = ("pellet01_state01".as_bytes(), "pellet02".as_bytes());
+        let range_proof = trie.get_range_proof(&root_hash, start, end, 10).unwrap();
+        assert_eq!(range_proof.entries.len(), 4);
+        assert!(!verify_range_proof::<T::Hasher, _>(&root_hash, start, end, &range_proof).unwrap());
+
+        // 遗漏了中间的数据，验证失败
+        let mut missing = range_proof.clone();
+        missing.entries.remove(1);
+        let result = verify_range_proof::<T::Hasher, _>(&root_hash, start, end, &missing);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 篡改了数据，验证失败
+        let mut tampered = range_proof.clone();
+        tampered.entries[0].1 = "value99".to_string();
+        let result = verify_range_proof::<T::Hasher, _>(&root_hash, start, end, &tampered);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 缩小了右边界来隐藏最后一条数据，验证失败
+        let mut truncated = range_proof.clone();
+        truncated.entries.pop();
+        truncated.last_key = truncated.entries.last().unwrap().0.clone();
+        let result = verify_range_proof::<T::Hasher, _>(&root_hash, start, end, &truncated);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 缺少边界路径上的节点，验证失败
+        let mut incomplete = range_proof;
+        incomplete.proof = Proof::new();
+        let result = verify_range_proof::<T::Hasher, _>(&root_hash, start, end, &incomplete);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+    }
+
+    #[test]
+    fn memory_prefix_proof_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        prefix_proof_works(&mut trie);
+    }
+
+    fn prefix_proof_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        let data = [
+            ("pellet01_state01_key01", "value01".to_string()),
+            ("pellet01_state01_key02", "value02".to_string()),
+            ("pellet01_state02_key01", "value03".to_string()),
+            ("pellet01_state02_key02", "value04".to_string()),
+            ("pellet02_state01_key01", "value05".to_string()),
+        ];
+        for (key, value) in data[..4].iter() {
+            trie.insert(key, value.clone()).unwrap();
+        }
+        let root_hash1 = trie.commit().unwrap().unwrap();
+        trie.insert(data[4].0, data[4].1.clone()).unwrap();
+        let root_hash2 = trie.commit().unwrap().unwrap();
+
+        // 获得前缀 proof 并验证
+        let prefix = b"pellet01_state02";
+        let proof_db = trie.get_prefix_proof(&root_hash2, prefix).unwrap();
+        let items =
+            verify_prefix_proof::<T::Hasher, String>(&root_hash2, &proof_db, prefix).unwrap();
+        let expected: Vec<_> = data[2..4]
+            .iter()
+            .map(|(key, value)| (key.as_bytes().to_vec(), value.clone()))
+            .collect();
+        assert_eq!(items, expected);
+
+        // 没有匹配数据的前缀，也可以证明
+        let prefix = b"pellet03";
+        let proof_db = trie.get_prefix_proof(&root_hash2, prefix).unwrap();
+        let items =
+            verify_prefix_proof::<T::Hasher, String>(&root_hash2, &proof_db, prefix).unwrap();
+        assert!(items.is_empty());
+
+        // 用另一个前缀的 proof 来验证，子树不完整，验证失败
+        let proof_db = trie
+            .get_prefix_proof(&root_hash2, b"pellet01_state01")
+            .unwrap();
+        let result =
+            verify_prefix_proof::<T::Hasher, String>(&root_hash2, &proof_db, b"pellet01_state02");
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 篡改根节点，验证失败
+        let mut proof_db = trie.get_prefix_proof(&root_hash2, b"pellet01").unwrap();
+        let bin_node = trie.db_ref().get(&root_hash1).unwrap().unwrap();
+        proof_db.insert(root_hash2, bin_node).unwrap();
+        let result = verify_prefix_proof::<T::Hasher, String>(&root_hash2, &proof_db, b"pellet01");
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+    }
+
     #[test]
     fn memory_proof_works() {
         let mut trie = MemoryTrie::<&'static str, String>::new();
         proof_works(&mut trie);
     }
 
+    #[cfg(feature = "keccak")]
+    #[test]
+    fn memory_keccak_proof_works() {
+        let mut trie = MemoryTrie::<&'static str, String, KeccakHasher>::new();
+        proof_works(&mut trie);
+        let mut trie = MemoryTrie::<&'static str, String, KeccakHasher>::new();
+        range_proof_works(&mut trie);
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn memory_sha256_proof_works() {
+        let mut trie = MemoryTrie::<&'static str, String, Sha256Hasher>::new();
+        proof_works(&mut trie);
+        let mut trie = MemoryTrie::<&'static str, String, Sha256Hasher>::new();
+        range_proof_works(&mut trie);
+    }
+
+    /// 只用于测试的 hash 函数，将 Blake2b 的结果反转
+    struct ReversedHasher;
+
+    impl TrieHasher for ReversedHasher {
+        fn hash(data: &[u8]) -> HashValue {
+            let mut hash_value = util::hash(data);
+            hash_value.reverse();
+            hash_value
+        }
+    }
+
+    #[test]
+    fn memory_hasher_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        let mut reversed_trie = MemoryTrie::<&'static str, String, ReversedHasher>::new();
+        let key = "pellet01_state01_key01";
+        trie.insert(key, "value01".to_string()).unwrap();
+        reversed_trie.insert(key, "value01".to_string()).unwrap();
+
+        // 同样的数据使用不同的 hash 函数得到不同的 root hash
+        let root_hash = trie.commit().unwrap().unwrap();
+        let reversed_root_hash = reversed_trie.commit().unwrap().unwrap();
+        assert_ne!(root_hash, reversed_root_hash);
+
+        // 使用生成 proof 的 trie 的 hash 函数才能验证 proof
+        let (_, proof) = reversed_trie
+            .get_compact_proof(&reversed_root_hash, &key)
+            .unwrap();
+        let value =
+            verify_compact_proof::<ReversedHasher, _, String>(&reversed_root_hash, &proof, &key);
+        assert_eq!(value.unwrap(), Verified::Present("value01".to_string()));
+        let result =
+            verify_compact_proof::<Blake2bHasher, _, String>(&reversed_root_hash, &proof, &key);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+    }
+
+    fn inline_works<'a, T>(trie: &mut T)
+    where
+        T: Trie<&'a str, String>,
+    {
+        trie.insert("do", "verb".to_string()).unwrap();
+        let root_hash1 = trie.commit().unwrap().unwrap();
+        trie.insert("dog", "puppy".to_string()).unwrap();
+        let root_hash2 = trie.commit().unwrap().unwrap();
+
+        // 扩展节点和分支节点存入数据库，叶子节点内嵌在分支节点里
+        assert_eq!(count_nodes(trie.db_ref(), root_hash2), 2);
+        let (exists, proof) = trie.get_compact_proof(&root_hash2, &"dog").unwrap();
+        assert!(exists);
+        assert_eq!(proof.nodes().len(), 2);
+
+        // 内嵌的叶子节点可以被查询和验证
+        assert_eq!(trie.get_value(&"dog").unwrap(), Some("puppy".to_string()));
+        let value = verify_compact_proof::<T::Hasher, _, String>(&root_hash2, &proof, &"dog");
+        assert_eq!(value.unwrap(), Verified::Present("puppy".to_string()));
+        let value = verify_compact_proof::<T::Hasher, _, String>(&root_hash2, &proof, &"dot");
+        assert_eq!(value.unwrap(), Verified::ProvablyAbsent);
+
+        // 删除内嵌的叶子节点之后，root hash 与插入之前相同
+        assert_eq!(trie.remove(&"dog").unwrap(), Some("puppy".to_string()));
+        assert_eq!(trie.commit().unwrap().unwrap(), root_hash1);
+    }
+
+    #[test]
+    fn memory_inline_works() {
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        inline_works(&mut trie);
+    }
+
+    #[test]
+    fn memory_root_hash_golden_works() {
+        // root hash 只取决于节点编码和 hash 函数，节点编码变化时这个测试会失败
+        // trie 的结构为: 扩展节点 [6, 4, 6, f] -> 分支节点 (value 为 "verb") -> 叶子节点 [7] (value 为 "puppy")
+        // 叶子节点的编码小于 32 字节，内嵌在分支节点里
+        let mut trie = MemoryTrie::<&'static str, String>::new();
+        trie.insert("dog", "puppy".to_string()).unwrap();
+        trie.insert("do", "verb".to_string()).unwrap();
+        let root_hash = trie.commit().unwrap().unwrap();
+        assert_eq!(
+            hex::encode(root_hash),
+            "3c558d69991c9659b6bab2053568cda633e6f485ef6b4c686c560ceac728a7da"
+        );
+        // 解码之后重新编码得到同样的字节
+        let bin_node = trie.db_ref().get(&root_hash).unwrap().unwrap();
+        assert_eq!(TrieNode::decode(&bin_node).unwrap().encode(), bin_node);
+    }
+
     fn proof_works<'a, T>(trie: &mut T)
     where
         T: Trie<&'a str, String>,
@@ -251,27 +1916,50 @@ mod tests {
         // 检查数据是否存在
         assert!(exists);
         // 验证 proof
-        let value = verify_proof::<_, String>(&root_hash2, &proof_db, &kv1.0)
-            .unwrap()
-            .unwrap();
+        let value = verify_proof::<T::Hasher, _, String>(&root_hash2, &proof_db, &kv1.0).unwrap();
         // 检查 value 是否正确
-        assert_eq!(value, kv1.1);
+        assert_eq!(value, Verified::Present(kv1.1.clone()));
 
         let (exists, proof_db) = trie.get_proof(&root_hash2, &kv2.0).unwrap();
         assert!(exists);
-        let value = verify_proof::<_, String>(&root_hash2, &proof_db, &kv2.0)
-            .unwrap()
-            .unwrap();
-        assert_eq!(value, kv2.1);
+        let value = verify_proof::<T::Hasher, _, String>(&root_hash2, &proof_db, &kv2.0).unwrap();
+        assert_eq!(value, Verified::Present(kv2.1.clone()));
 
         let (exists, proof_db) = trie.get_proof(&root_hash1, &kv1.0).unwrap();
         assert!(exists);
-        let value = verify_proof::<_, String>(&root_hash1, &proof_db, &kv1.0)
-            .unwrap()
-            .unwrap();
-        assert_eq!(value, kv1.1);
+        let value = verify_proof::<T::Hasher, _, String>(&root_hash1, &proof_db, &kv1.0).unwrap();
+        assert_eq!(value, Verified::Present(kv1.1.clone()));
+
+        // 数据二在 root hash 1 里不存在，proof 可以证明这一点
+        let (exists, proof_db) = trie.get_proof(&root_hash1, &kv2.0).unwrap();
+        assert!(!exists);
+        let value = verify_proof::<T::Hasher, _, String>(&root_hash1, &proof_db, &kv2.0).unwrap();
+        assert_eq!(value, Verified::ProvablyAbsent);
 
-        let (exists, _) = trie.get_proof(&root_hash1, &kv2.0).unwrap();
+        // 在分支节点的空 child 处分叉的 key, 同样可以证明不存在
+        let key = "0000aaaa";
+        let (exists, proof_db) = trie.get_proof(&root_hash2, &key).unwrap();
         assert!(!exists);
+        let value = verify_proof::<T::Hasher, _, String>(&root_hash2, &proof_db, &key).unwrap();
+        assert_eq!(value, Verified::ProvablyAbsent);
+
+        // 空的 proof 无法得出结论
+        let result =
+            verify_proof::<T::Hasher, _, String>(&root_hash2, &MemoryDatabase::new(), &kv2.0);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 篡改根节点，把另一个版本的根节点放在 root hash 2 下，验证失败
+        let (_, mut proof_db) = trie.get_proof(&root_hash2, &kv1.0).unwrap();
+        let bin_node = trie.db_ref().get(&root_hash1).unwrap().unwrap();
+        proof_db.insert(root_hash2, bin_node).unwrap();
+        let result = verify_proof::<T::Hasher, _, String>(&root_hash2, &proof_db, &kv1.0);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
+
+        // 只包含根节点的 proof 是不完整的
+        let mut proof_db = MemoryDatabase::new();
+        let bin_node = trie.db_ref().get(&root_hash2).unwrap().unwrap();
+        proof_db.insert(root_hash2, bin_node).unwrap();
+        let result = verify_proof::<T::Hasher, _, String>(&root_hash2, &proof_db, &kv2.0);
+        assert!(matches!(result, Err(TrieError::InvalidProof)));
     }
 }
@@ -1,12 +1,11 @@
 use crate::database::Database;
-use crate::trie::node::{TrieNode, TrieNodeLink};
-use crate::trie::util;
-use crate::{Result, NibbleSlice};
+use crate::trie::node::{Extension, Node, TrieNode, TrieNodeLink};
+use crate::trie::TrieHasher;
+use crate::{NibbleSlice, Result};
 use array_init::array_init;
-use serde::{Deserialize, Serialize};
 
 /// 分支节点
-#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Hash)]
 pub struct Branch {
     pub children: [TrieNodeLink; 16],
     pub value: Option<Vec<u8>>,
@@ -28,7 +27,7 @@ impl Branch {
         value: Vec<u8>,
     ) -> Result<TrieNode> {
         // 如果 key_nb 为空，那么我们只能将 value 放入 branch 的 value 属性中
-        if key_nb.len() == 0 {
+        if key_nb.is_empty() {
             self.value = value.into();
             return Ok(self.into());
         }
@@ -39,7 +38,7 @@ impl Branch {
         let trie_node_link =
             std::mem::replace(&mut self.children[idx[0] as usize], TrieNodeLink::Empty);
         // 向 trie_node_link 中插入数据
-        let child = trie_node_link.insert(db, key_nb.into(), value)?;
+        let child = trie_node_link.insert(db, key_nb, value)?;
         // 将 child 放回 children 数组中
         self.set_child(idx[0] as usize, child);
         Ok(self.into())
@@ -51,15 +50,15 @@ impl Branch {
     }
 
     /// 将分支节点压缩, 压缩的过程就是将节点存入数据库中, 并返回一个 TrieNodeLink::HashValue
-    pub fn collapse(self, db: &mut impl Database) -> Result<TrieNodeLink> {
+    pub fn collapse<H>(self, db: &mut impl Database) -> Result<TrieNodeLink>
+    where
+        H: TrieHasher,
+    {
         // 使用解构语法将 self 分解成三个部分
-        // 解构也可以直接写在函数的参数中，如: 
+        // 解构也可以直接写在函数的参数中，如:
         // pub fn collapse(Branch { children, value }: Self, db: &mut impl Database) -> Result<TrieNodeLink> {
         // 这两种都可以，按自己的喜好及团队的要求决定
-        let Branch {
-            children,
-            value,
-        } = self;
+        let Branch { children, value } = self;
 
         // 创建一个新的 branch
         let mut branch = Branch::new();
@@ -68,13 +67,13 @@ impl Branch {
 
         // 遍历 children 数组, 将其中的 TrieNodeLink::Branch 节点压缩
         for (i, child) in children.into_iter().enumerate() {
-            branch.set_child(i, child.collapse(db).unwrap());
+            branch.set_child(i, child.collapse_inline::<H>(db).unwrap());
         }
 
         // 将 branch 转换成 Vec<u8>
-        let data: Vec<u8> = branch.try_into()?;
+        let data: Vec<u8> = branch.into();
         // 计算 hash 值
-        let hash_value = util::hash(&data);
+        let hash_value = H::hash(&data);
 
         // 将数据存入数据库中
         db.insert(hash_value, data)?;
@@ -85,7 +84,7 @@ impl Branch {
     /// 从 branch 中获取数据
     pub fn get_value(&self, db: &impl Database, key_nb: &NibbleSlice) -> Result<Option<Vec<u8>>> {
         // 如果 key_nb 为空，那么我们只能从 branch 的 value 属性中获取数据
-        if key_nb.len() == 0 {
+        if key_nb.is_empty() {
             return Ok(self.value.clone());
         }
 
@@ -94,7 +93,7 @@ impl Branch {
         // 从 children 数组中取出对应的 trie_node_link
         let child = &self.children[idx[0] as usize];
         // 从 trie_node_link 中获取数据
-        child.get_value(db, &key_nb)
+        child.get_value(db, key_nb)
     }
 
     /// 从 branch 中获取 proof
@@ -105,7 +104,7 @@ impl Branch {
         key_nb: &NibbleSlice,
     ) -> Result<bool> {
         // 如果 key_nb 为空，那么我们只能从 branch 的 value 属性中获取数据
-        if key_nb.len() == 0 {
+        if key_nb.is_empty() {
             // 如果 value 为 None，那么返回 false
             return match self.value {
                 Some(_) => Ok(true),
@@ -118,16 +117,94 @@ impl Branch {
         // 从 children 数组中取出对应的 trie_node_link
         let child = &self.children[idx[0] as usize];
         // 从 trie_node_link 中获取数据
-        let exists = child.get_proof(db, proof_db, &key_nb)?;
+        let exists = child.get_proof(db, proof_db, key_nb)?;
         Ok(exists)
     }
+
+    /// 从 branch 中获取前缀 proof
+    pub fn get_prefix_proof(
+        &self,
+        db: &impl Database,
+        proof_db: &mut impl Database,
+        prefix_nb: &NibbleSlice,
+    ) -> Result<()> {
+        // 如果 prefix_nb 为空，那么 branch 下所有的数据都以 prefix 开头，需要收集所有的 children
+        if prefix_nb.is_empty() {
+            for child in self.children.iter() {
+                child.get_prefix_proof(db, proof_db, prefix_nb)?;
+            }
+            return Ok(());
+        }
+
+        // 将 prefix_nb 的第一个 nibble 取出来，用来决定从哪个 child 中获取 proof
+        let (idx, prefix_nb) = prefix_nb.split_at(1);
+        self.children[idx[0] as usize].get_prefix_proof(db, proof_db, prefix_nb)
+    }
+
+    /// 从分支节点中删除数据, 返回删除后的 TrieNodeLink 和被删除的值
+    pub fn remove(
+        mut self,
+        db: &impl Database,
+        key_nb: &NibbleSlice,
+    ) -> Result<(TrieNodeLink, Option<Vec<u8>>)> {
+        let value = if key_nb.is_empty() {
+            // 如果 key_nb 为空，那么要删除的是 branch 的 value 属性
+            self.value.take()
+        } else {
+            // 将 key_nb 的第一个 nibble 取出来，用来决定从哪个 child 中删除数据
+            let (idx, key_nb) = key_nb.split_at(1);
+            // 从 children 数组中取出对应的 trie_node_link
+            let trie_node_link = std::mem::take(&mut self.children[idx[0] as usize]);
+            // 从 trie_node_link 中删除数据
+            let (child, value) = trie_node_link.remove(db, key_nb)?;
+            // 将 child 放回 children 数组中
+            self.set_child(idx[0] as usize, child);
+            value
+        };
+
+        // 如果没有删除任何数据，分支节点保持不变
+        if value.is_none() {
+            return Ok((self.into(), None));
+        }
+        // 删除之后分支节点可能只剩下一个 child 或 value，需要重新整理
+        Ok((self.normalize(db)?, value))
+    }
+
+    /// 整理分支节点，只剩下一个 child 或 value 的分支节点会被压缩成扩展节点或叶子节点
+    pub fn normalize(self, db: &impl Database) -> Result<TrieNodeLink> {
+        let Branch {
+            mut children,
+            value,
+        } = self;
+
+        // 找出所有不为空的 child 的索引
+        let indexes: Vec<usize> = children
+            .iter()
+            .enumerate()
+            .filter(|(_, child)| !matches!(child, TrieNodeLink::Empty))
+            .map(|(idx, _)| idx)
+            .collect();
+
+        match (indexes.as_slice(), value) {
+            // 既没有 child 也没有 value，分支节点变为空
+            ([], None) => Ok(TrieNodeLink::Empty),
+            // 只剩下 value，分支节点变为 rest_of_key 为空的叶子节点
+            ([], Some(value)) => Ok(Node::new(vec![], value).into()),
+            // 只剩下一个 child，将 child 的索引作为 partial_key 构建扩展节点，再交给扩展节点与 child 合并
+            ([idx], None) => Extension {
+                partial_key: vec![*idx as u8],
+                branch: std::mem::take(&mut children[*idx]),
+            }
+            .normalize(db),
+            // 其他情况，分支节点保持不变
+            (_, value) => Ok(Branch { children, value }.into()),
+        }
+    }
 }
 
 /// 将 Branch 转换成 Vec<u8>
-impl TryFrom<Branch> for Vec<u8> {
-    type Error = bincode::Error;
-
-    fn try_from(value: Branch) -> std::result::Result<Self, Self::Error> {
-        bincode::serialize(&value)
+impl From<Branch> for Vec<u8> {
+    fn from(value: Branch) -> Self {
+        TrieNode::from(value).encode()
     }
 }
@@ -0,0 +1,373 @@
+use super::{Branch, Extension, Node, TrieNode, TrieNodeLink};
+use crate::{HashValue, NibbleSlice, NibbleVec, Result, TrieError};
+
+/// 节点编码的版本号，保存在编码的第一个字节里
+/// 修改编码格式时必须增加版本号，旧版本的节点会被拒绝，而不是被错误地解析
+pub const NODE_CODEC_VERSION: u8 = 2;
+
+// 节点类型的标记
+const TAG_NODE: u8 = 0;
+const TAG_EXTENSION: u8 = 1;
+const TAG_BRANCH: u8 = 2;
+
+// TrieNodeLink 的标记
+const LINK_EMPTY: u8 = 0;
+const LINK_HASH_VALUE: u8 = 1;
+const LINK_INLINE: u8 = 2;
+
+/// 内嵌节点最多嵌套的层数
+/// 内嵌节点的编码小于 hash 的长度，正常的节点不会嵌套很多层，限制层数避免解码错误的数据时递归导致栈溢出
+const MAX_INLINE_DEPTH: usize = 64;
+
+/// 节点的二进制编码，不依赖 serde 和 bincode，节点的 hash 就是这个编码的 hash
+///
+/// ```text
+/// 节点      := 版本号(1 字节) 节点体
+/// 节点体    := 0x00 路径 字节串                 叶子节点: rest_of_key, value
+///            | 0x01 路径 链接                   扩展节点: partial_key, branch
+///            | 0x02 链接 * 16 (0x00 | 0x01 字节串)  分支节点: children, value
+/// 链接      := 0x00                             TrieNodeLink::Empty
+///            | 0x01 hash(32 字节)               TrieNodeLink::HashValue
+///            | 0x02 节点体                      TrieNodeLink::Inline，内嵌的子节点，
+///                                               还没有压缩的 TrieNodeLink::TrieNode 也这样编码
+/// 路径      := nibble 数量(变长整数) 每个字节保存两个 nibble，高 4 位在前，数量为奇数时最后 4 位补 0
+/// 字节串    := 长度(变长整数) 数据
+/// 变长整数  := LEB128 编码的无符号整数，每个字节保存 7 位，低位在前，最高位为 1 表示后面还有字节
+/// ```
+///
+/// 解码时拒绝所有不规范的编码，包括多余的字节、不是最短的变长整数和不为 0 的补位，
+/// 所以每个节点只有一种编码
+impl TrieNode {
+    /// 将节点编码为字节
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![NODE_CODEC_VERSION];
+        encode_body(self, &mut buf);
+        buf
+    }
+
+    /// 从字节解码节点
+    pub fn decode(buf: &[u8]) -> Result<Self> {
+        let mut reader = Reader { buf, depth: 0 };
+        let version = reader.byte()?;
+        if version != NODE_CODEC_VERSION {
+            return Err(TrieError::UnsupportedNodeVersion(version));
+        }
+        let trie_node = reader.body()?;
+        if !reader.buf.is_empty() {
+            return Err(TrieError::InvalidNode);
+        }
+        Ok(trie_node)
+    }
+}
+
+/// 节点内嵌在父节点里时不包含版本号，内嵌之后的长度小于 hash 的长度时，节点应该内嵌
+pub(crate) fn can_inline(bin_node: &[u8]) -> bool {
+    bin_node.len() - 1 < std::mem::size_of::<HashValue>()
+}
+
+fn encode_body(trie_node: &TrieNode, buf: &mut Vec<u8>) {
+    match trie_node {
+        TrieNode::Node(Node { rest_of_key, value }) => {
+            buf.push(TAG_NODE);
+            encode_path(rest_of_key, buf);
+            encode_bytes(value, buf);
+        }
+        TrieNode::Extension(Extension {
+            partial_key,
+            branch,
+        }) => {
+            buf.push(TAG_EXTENSION);
+            encode_path(partial_key, buf);
+            encode_link(branch, buf);
+        }
+        TrieNode::Branch(Branch { children, value }) => {
+            buf.push(TAG_BRANCH);
+            for child in children.iter() {
+                encode_link(child, buf);
+            }
+            match value {
+                Some(value) => {
+                    buf.push(1);
+                    encode_bytes(value, buf);
+                }
+                None => buf.push(0),
+            }
+        }
+    }
+}
+
+fn encode_link(link: &TrieNodeLink, buf: &mut Vec<u8>) {
+    match link {
+        TrieNodeLink::Empty => buf.push(LINK_EMPTY),
+        TrieNodeLink::HashValue(hash_value) => {
+            buf.push(LINK_HASH_VALUE);
+            buf.extend_from_slice(hash_value);
+        }
+        TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+            buf.push(LINK_INLINE);
+            encode_body(trie_node, buf);
+        }
+    }
+}
+
+fn encode_path(nibbles: &NibbleSlice, buf: &mut Vec<u8>) {
+    encode_varint(nibbles.len() as u64, buf);
+    buf.extend(
+        nibbles
+            .chunks(2)
+            .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0)),
+    );
+}
+
+fn encode_bytes(bytes: &[u8], buf: &mut Vec<u8>) {
+    encode_varint(bytes.len() as u64, buf);
+    buf.extend_from_slice(bytes);
+}
+
+fn encode_varint(mut n: u64, buf: &mut Vec<u8>) {
+    while n >= 0x80 {
+        buf.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+/// 从字节里依次读取数据，数据不完整或者不规范时返回 TrieError::InvalidNode
+struct Reader<'a> {
+    buf: &'a [u8],
+    // 当前所在的内嵌节点的层数
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.buf.len() < len {
+            return Err(TrieError::InvalidNode);
+        }
+        let (data, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Ok(data)
+    }
+
+    fn body(&mut self) -> Result<TrieNode> {
+        let trie_node = match self.byte()? {
+            TAG_NODE => Node {
+                rest_of_key: self.path()?,
+                value: self.bytes()?,
+            }
+            .into(),
+            TAG_EXTENSION => Extension {
+                partial_key: self.path()?,
+                branch: self.link()?,
+            }
+            .into(),
+            TAG_BRANCH => {
+                let mut branch = Branch::new();
+                for child in branch.children.iter_mut() {
+                    *child = self.link()?;
+                }
+                branch.value = match self.byte()? {
+                    0 => None,
+                    1 => Some(self.bytes()?),
+                    _ => return Err(TrieError::InvalidNode),
+                };
+                branch.into()
+            }
+            _ => return Err(TrieError::InvalidNode),
+        };
+        Ok(trie_node)
+    }
+
+    fn link(&mut self) -> Result<TrieNodeLink> {
+        match self.byte()? {
+            LINK_EMPTY => Ok(TrieNodeLink::Empty),
+            LINK_HASH_VALUE => {
+                let hash_value: HashValue = self.take(32)?.try_into().unwrap();
+                Ok(TrieNodeLink::HashValue(hash_value))
+            }
+            LINK_INLINE => {
+                if self.depth == MAX_INLINE_DEPTH {
+                    return Err(TrieError::InvalidNode);
+                }
+                self.depth += 1;
+                let trie_node = self.body()?;
+                self.depth -= 1;
+                Ok(TrieNodeLink::Inline(Box::new(trie_node)))
+            }
+            _ => Err(TrieError::InvalidNode),
+        }
+    }
+
+    fn path(&mut self) -> Result<NibbleVec> {
+        let len = self.len()?;
+        let packed = self.take(len.div_ceil(2))?;
+        // 数量为奇数时，最后一个字节的低 4 位必须为 0
+        if len % 2 == 1 && packed[packed.len() - 1] & 0x0f != 0 {
+            return Err(TrieError::InvalidNode);
+        }
+        let mut nibbles = Vec::with_capacity(len);
+        for byte in packed {
+            nibbles.push(byte >> 4);
+            nibbles.push(byte & 0x0f);
+        }
+        nibbles.truncate(len);
+        Ok(nibbles)
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.len()?;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// 读取长度，长度不能超过剩下的字节数量，避免为错误的数据分配大量内存
+    fn len(&mut self) -> Result<usize> {
+        let len = self.varint()?;
+        if len > self.buf.len() as u64 * 2 {
+            return Err(TrieError::InvalidNode);
+        }
+        Ok(len as usize)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            let bits = (byte & 0x7f) as u64;
+            if shift == 63 && bits > 1 {
+                return Err(TrieError::InvalidNode);
+            }
+            n |= bits << shift;
+            if byte & 0x80 == 0 {
+                // 最后一个字节为 0 说明编码不是最短的
+                if byte == 0 && shift > 0 {
+                    return Err(TrieError::InvalidNode);
+                }
+                return Ok(n);
+            }
+        }
+        Err(TrieError::InvalidNode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trie::{Blake2bHasher, TrieHasher};
+
+    fn check_golden(trie_node: TrieNode, expected: &str) {
+        let encoded = trie_node.encode();
+        assert_eq!(hex::encode(&encoded), expected);
+        let decoded = TrieNode::decode(&encoded).unwrap();
+        assert_eq!(decoded.encode(), encoded);
+    }
+
+    #[test]
+    fn node_codec_golden_works() {
+        // 叶子节点，奇数个 nibble
+        check_golden(
+            Node::new(vec![1, 2, 3], vec![0xaa, 0xbb]).into(),
+            "020003123002aabb",
+        );
+        // rest_of_key 为空的叶子节点，value 的长度需要两个字节的变长整数
+        let mut expected = "020000ac02".to_string();
+        expected.push_str(&"ff".repeat(300));
+        check_golden(Node::new(vec![], vec![0xff; 300]).into(), &expected);
+        // 扩展节点
+        check_golden(
+            Extension {
+                partial_key: vec![0xa, 0xb],
+                branch: TrieNodeLink::HashValue([0x11; 32]),
+            }
+            .into(),
+            &format!("020102ab01{}", "11".repeat(32)),
+        );
+        // 分支节点，第一个 child 为 hash，其他为空，带有 value
+        let mut branch = Branch::new();
+        branch.children[0] = TrieNodeLink::HashValue([0x22; 32]);
+        branch.value = Some(vec![0x01]);
+        check_golden(
+            branch.into(),
+            &format!("020201{}{}010101", "22".repeat(32), "00".repeat(15)),
+        );
+        // 分支节点，最后一个 child 为内嵌的叶子节点，没有 value
+        let mut branch = Branch::new();
+        branch.children[15] = TrieNodeLink::Inline(Box::new(Node::new(vec![4], vec![0x05]).into()));
+        check_golden(
+            branch.into(),
+            &format!("0202{}02000140010500", "00".repeat(15)),
+        );
+    }
+
+    #[test]
+    fn node_codec_hash_golden_works() {
+        // 节点的 hash 只取决于编码，编码变化时这个测试会失败
+        let trie_node: TrieNode = Node::new(vec![1, 2, 3], vec![0xaa, 0xbb]).into();
+        assert_eq!(
+            hex::encode(Blake2bHasher::hash(&trie_node.encode())),
+            "5dba8928397328d1157e9313ee2b1d6c968270df566707b6c7465dfecbd1094a"
+        );
+    }
+
+    #[test]
+    fn node_codec_rejects_invalid_encoding() {
+        let valid = hex::decode("020003123002aabb").unwrap();
+        assert!(TrieNode::decode(&valid).is_ok());
+
+        // 不支持的版本号
+        let mut buf = valid.clone();
+        buf[0] = 1;
+        assert!(matches!(
+            TrieNode::decode(&buf),
+            Err(TrieError::UnsupportedNodeVersion(1))
+        ));
+
+        let invalid = [
+            // 空的数据
+            "",
+            // 只有版本号
+            "02",
+            // 未知的节点类型
+            "0203",
+            // 多余的字节
+            "020003123002aabb00",
+            // 数据不完整
+            "020003123002aa",
+            // 补位不为 0
+            "020003123102aabb",
+            // 不是最短的变长整数
+            "02008300123002aabb",
+            // 未知的链接类型
+            "02010003",
+            // 分支节点的 value 标记错误
+            &format!("0202{}02", "00".repeat(16)),
+        ];
+        for hex_str in invalid.iter() {
+            let buf = hex::decode(hex_str).unwrap();
+            assert!(
+                matches!(TrieNode::decode(&buf), Err(TrieError::InvalidNode)),
+                "{hex_str}"
+            );
+        }
+    }
+
+    #[test]
+    fn node_codec_rejects_deep_inline_nesting() {
+        // depth 层内嵌的扩展节点，最里面的扩展节点的 branch 为空
+        let nested =
+            |depth: usize| hex::decode(format!("02{}010000", "010002".repeat(depth))).unwrap();
+        assert!(TrieNode::decode(&nested(MAX_INLINE_DEPTH)).is_ok());
+        assert!(matches!(
+            TrieNode::decode(&nested(MAX_INLINE_DEPTH + 1)),
+            Err(TrieError::InvalidNode)
+        ));
+        // 嵌套很深的数据不会导致栈溢出
+        assert!(matches!(
+            TrieNode::decode(&nested(1_000_000)),
+            Err(TrieError::InvalidNode)
+        ));
+    }
+}
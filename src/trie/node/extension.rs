@@ -1,12 +1,10 @@
 use crate::database::Database;
-use crate::trie::node::{Branch, TrieNode, TrieNodeLink};
-use crate::trie::util;
-use crate::TrieError;
+use crate::trie::node::{Branch, Node, TrieNode, TrieNodeLink};
+use crate::trie::{util, TrieHasher};
 use crate::{NibbleSlice, NibbleVec, Result};
-use serde::{Deserialize, Serialize};
 
 /// 扩展节点
-#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Hash)]
 pub struct Extension {
     pub partial_key: NibbleVec,
     pub branch: TrieNodeLink,
@@ -27,7 +25,7 @@ impl Extension {
         } = self;
         // 解析出共同的前缀
         let (shared, rest_of_partial_key, rest_of_key_nb) =
-            util::parse_nibble_slices_shared_portion(&partial_key, &key_nb);
+            util::parse_nibble_slices_shared_portion(&partial_key, key_nb);
 
         let trie_node = match rest_of_partial_key.len() {
             // 如果扩展节点的 rest_of_partial_key 为空, 说明扩展节点的 partial_key 是 key_nb 的子集
@@ -38,36 +36,31 @@ impl Extension {
             }
             // 将 extension 转换为 TrieNode
             .into(),
-            // 如果 rest_of_partial_key 只有一个 nibble 了，则直接将 old_branch 添加到新 Branch 对应的索引下
-            1 => {
-                // 构建一个新的 Branch
-                let mut branch = Branch::new();
-                // 将原来的 Branch 放在新 Branch 的对应索引下
-                branch.set_child(rest_of_partial_key[0] as usize, old_branch);
-                // 将新的键值对插入到新 Branch 中
-                let branch = branch.insert(db, rest_of_key_nb, value)?;
-                // 将 branch 转换为 TrieNode
-                branch.into()
-            }
-            // 如果 rest_of_partial_key 不只有一个 nibble
+            // 如果 rest_of_partial_key 不为空，说明 key_nb 与 partial_key 在 shared 之后分叉了
             _ => {
                 // 创建新的 Branch
                 let mut branch = Branch::new();
                 // 获得 rest_of_partial_key 的第一个 nibble
                 let (idx, rest_of_partial_key) = rest_of_partial_key.split_at(1);
-                // 创建新的 Extension, partial_key 为 rest_of_partial_key, branch 为原来的 Branch
-                let extension = Extension {
-                    partial_key: rest_of_partial_key.to_owned(),
-                    branch: old_branch.into(),
+                // 如果 rest_of_partial_key 只有一个 nibble 了，则直接将 old_branch 添加到新 Branch 对应的索引下
+                // 否则创建新的 Extension, partial_key 为剩余的 rest_of_partial_key, branch 为原来的 Branch
+                let child = if rest_of_partial_key.is_empty() {
+                    old_branch
+                } else {
+                    Extension {
+                        partial_key: rest_of_partial_key.to_owned(),
+                        branch: old_branch,
+                    }
+                    .into()
                 };
-                // 将新的 Extension 放在新的 Branch 下面
-                branch.set_child(idx[0] as usize, extension.into());
+                // 将 child 放在新的 Branch 下面
+                branch.set_child(idx[0] as usize, child);
                 // 将新的键值对插入到新的 Branch 中
                 let branch = branch.insert(db, rest_of_key_nb, value)?;
 
                 // 如果 shared 为空, 则直接返回 branch
-                if shared.len() == 0 {
-                    branch.into()
+                if shared.is_empty() {
+                    branch
                 } else {
                     // 否则返回另一个新的 Extension
                     // 新的 Extension 的 partial_key 为 shared, branch 为新的 Branch
@@ -84,7 +77,10 @@ impl Extension {
     }
 
     /// 将扩展节点压缩，压缩的过程就是将节点存入数据库中, 并返回一个 TrieNodeLink::HashValue
-    pub fn collapse(self, db: &mut impl Database) -> Result<TrieNodeLink> {
+    pub fn collapse<H>(self, db: &mut impl Database) -> Result<TrieNodeLink>
+    where
+        H: TrieHasher,
+    {
         // 解构
         let Extension {
             partial_key,
@@ -93,13 +89,13 @@ impl Extension {
         // 构建一个新的 Extension
         let extension = Extension {
             partial_key,
-            branch: branch.collapse(db)?,
+            branch: branch.collapse_inline::<H>(db)?,
         };
 
         // 将 Extension 转换为 Vec<u8>
-        let data: Vec<u8> = extension.try_into()?;
+        let data: Vec<u8> = extension.into();
         // 计算 hash 值
-        let hash_value = util::hash(&data);
+        let hash_value = H::hash(&data);
         // 将数据存入数据库中
         db.insert(hash_value, data)?;
         // 返回 TrieNodeLink::HashValue
@@ -138,13 +134,98 @@ impl Extension {
         // 委托给 branch 来处理
         self.branch.get_proof(db, proof_db, rest_of_key_nb)
     }
+
+    /// 从扩展节点获得前缀 proof
+    pub fn get_prefix_proof(
+        &self,
+        db: &impl Database,
+        proof_db: &mut impl Database,
+        prefix_nb: &NibbleSlice,
+    ) -> Result<()> {
+        // 解析出共同的前缀
+        let (shared, _, rest_of_prefix_nb) =
+            util::parse_nibble_slices_shared_portion(&self.partial_key, prefix_nb);
+
+        // 如果 prefix 以 partial_key 开头，委托给 branch 来处理剩余的 prefix
+        // 如果 prefix 在 partial_key 中间结束，剩余的 prefix 为空，branch 下所有的数据都以 prefix 开头
+        if shared.len() == self.partial_key.len() || rest_of_prefix_nb.is_empty() {
+            self.branch
+                .get_prefix_proof(db, proof_db, rest_of_prefix_nb)
+        } else {
+            // prefix 与 partial_key 分叉，没有以 prefix 开头的数据，扩展节点本身就是证明
+            Ok(())
+        }
+    }
+
+    /// 从扩展节点中删除数据, 返回删除后的 TrieNodeLink 和被删除的值
+    pub fn remove(
+        self,
+        db: &impl Database,
+        key_nb: &NibbleSlice,
+    ) -> Result<(TrieNodeLink, Option<Vec<u8>>)> {
+        // 如果 key_nb 不是以 partial_key 开头，则说明没有找到，扩展节点保持不变
+        if !key_nb.starts_with(&self.partial_key) {
+            return Ok((self.into(), None));
+        }
+
+        // 解构扩展节点
+        let Extension {
+            partial_key,
+            branch,
+        } = self;
+        // 委托给 branch 来处理
+        let (branch, value) = branch.remove(db, &key_nb[partial_key.len()..])?;
+        let extension = Extension {
+            partial_key,
+            branch,
+        };
+
+        // 如果没有删除任何数据，扩展节点保持不变
+        if value.is_none() {
+            return Ok((extension.into(), None));
+        }
+        // 删除之后 branch 可能变成了其他类型的节点，需要重新整理
+        Ok((extension.normalize(db)?, value))
+    }
+
+    /// 整理扩展节点，保证删除数据后 trie 的形状与从未插入过这条数据时相同
+    pub fn normalize(self, db: &impl Database) -> Result<TrieNodeLink> {
+        let Extension {
+            partial_key,
+            branch,
+        } = self;
+
+        let trie_node_link = match branch.resolve(db)? {
+            // 如果 branch 已经为空，那么扩展节点也为空
+            None => TrieNodeLink::Empty,
+            // 如果 branch 变成了叶子节点，那么将 partial_key 合并到叶子节点的 rest_of_key 中
+            Some(TrieNode::Node(Node { rest_of_key, value })) => {
+                Node::new([partial_key, rest_of_key].concat(), value).into()
+            }
+            // 如果 branch 变成了扩展节点，那么将两个相邻的扩展节点合并
+            Some(TrieNode::Extension(Extension {
+                partial_key: rest_of_partial_key,
+                branch,
+            })) => Extension {
+                partial_key: [partial_key, rest_of_partial_key].concat(),
+                branch,
+            }
+            .into(),
+            // 如果 branch 仍然是分支节点，那么扩展节点保持不变
+            Some(TrieNode::Branch(branch)) => Extension {
+                partial_key,
+                branch: branch.into(),
+            }
+            .into(),
+        };
+
+        Ok(trie_node_link)
+    }
 }
 
 /// 将 Extension 转换为 Vec<u8>
-impl TryFrom<Extension> for Vec<u8> {
-    type Error = TrieError;
-
-    fn try_from(value: Extension) -> std::result::Result<Self, Self::Error> {
-        Ok(bincode::serialize(&value)?)
+impl From<Extension> for Vec<u8> {
+    fn from(value: Extension) -> Self {
+        TrieNode::from(value).encode()
     }
 }
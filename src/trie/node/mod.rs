@@ -1,19 +1,23 @@
-use serde::{Deserialize, Serialize};
-
-use super::util;
+use super::TrieHasher;
 use crate::database::Database;
 use crate::{HashValue, NibbleSlice, Result, TrieError};
 
 mod branch;
+/// 节点的二进制编码
+mod codec;
 mod extension;
+#[allow(clippy::module_inception)]
 mod node;
 
 pub use branch::*;
+use codec::can_inline;
+pub use codec::NODE_CODEC_VERSION;
 pub use extension::*;
 pub use node::*;
 
 /// 表现一个 Trie 节点
-#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Hash)]
 pub enum TrieNode {
     Extension(Extension),
     Node(Node),
@@ -29,9 +33,9 @@ impl TrieNode {
         value: Vec<u8>,
     ) -> Result<Self> {
         match self {
-            TrieNode::Node(node) => Ok(node.insert(db, key_nb, value)?.into()),
-            TrieNode::Extension(extension) => Ok(extension.insert(db, key_nb, value)?.into()),
-            TrieNode::Branch(branch) => Ok(branch.insert(db, key_nb, value)?.into()),
+            TrieNode::Node(node) => node.insert(db, key_nb, value),
+            TrieNode::Extension(extension) => extension.insert(db, key_nb, value),
+            TrieNode::Branch(branch) => branch.insert(db, key_nb, value),
         }
     }
 
@@ -58,18 +62,153 @@ impl TrieNode {
         }
     }
 
+    /// 从 TrieNode 中获得前缀 proof
+    pub fn get_prefix_proof(
+        &self,
+        db: &impl Database,
+        proof_db: &mut impl Database,
+        prefix_nb: &NibbleSlice,
+    ) -> Result<()> {
+        match self {
+            // 叶子节点已经由上层放入 proof_db 中，没有需要继续收集的节点
+            TrieNode::Node(_) => Ok(()),
+            TrieNode::Extension(extension) => extension.get_prefix_proof(db, proof_db, prefix_nb),
+            TrieNode::Branch(branch) => branch.get_prefix_proof(db, proof_db, prefix_nb),
+        }
+    }
+
+    /// 将 TrieNode 展开，展开是压缩的逆过程，将能在数据库中找到的 HashValue 替换为 TrieNode
+    pub fn expand(self, db: &impl Database) -> Result<Self> {
+        let trie_node = match self {
+            TrieNode::Node(_) => self,
+            TrieNode::Extension(Extension {
+                partial_key,
+                branch,
+            }) => Extension {
+                partial_key,
+                branch: branch.expand(db)?,
+            }
+            .into(),
+            TrieNode::Branch(Branch {
+                children: old_children,
+                value,
+            }) => {
+                let mut children: [TrieNodeLink; 16] =
+                    array_init::array_init(|_| TrieNodeLink::Empty);
+                for (idx, child) in old_children.into_iter().enumerate() {
+                    children[idx] = child.expand(db)?;
+                }
+                Branch { children, value }.into()
+            }
+        };
+
+        Ok(trie_node)
+    }
+
+    /// 从 TrieNode 中删除数据, 返回删除后的 TrieNodeLink 和被删除的值
+    pub fn remove(
+        self,
+        db: &impl Database,
+        key_nb: &NibbleSlice,
+    ) -> Result<(TrieNodeLink, Option<Vec<u8>>)> {
+        match self {
+            TrieNode::Node(node) => node.remove(key_nb),
+            TrieNode::Extension(extension) => extension.remove(db, key_nb),
+            TrieNode::Branch(branch) => branch.remove(db, key_nb),
+        }
+    }
+
+    /// 读取 key 的路径上的子节点，见 TrieNodeLink::load_path
+    pub fn load_path(&mut self, db: &impl Database, key_nb: &NibbleSlice) -> Result<()> {
+        match self {
+            TrieNode::Node(_) => Ok(()),
+            TrieNode::Extension(extension) => {
+                // key_nb 不是以 partial_key 开头时，插入和删除都不会再访问 branch
+                if !key_nb.starts_with(&extension.partial_key) {
+                    return Ok(());
+                }
+                let key_nb = &key_nb[extension.partial_key.len()..];
+                extension.branch.load_path(db, key_nb)
+            }
+            TrieNode::Branch(branch) => {
+                // 删除之后分支节点可能只剩下一个 child，这个 child 会被读取出来与分支节点合并
+                let count = branch
+                    .children
+                    .iter()
+                    .filter(|child| !matches!(child, TrieNodeLink::Empty))
+                    .count();
+                if count <= 2 {
+                    for child in branch.children.iter_mut() {
+                        child.load(db)?;
+                    }
+                }
+                match key_nb.split_first() {
+                    Some((idx, key_nb)) => branch.children[*idx as usize].load_path(db, key_nb),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    /// 获得 TrieNode 直接引用的子节点的 hash, 压缩后的节点只通过 HashValue 引用子节点
+    /// 内嵌的子节点不在数据库里，返回的是内嵌的子节点引用的 hash
+    pub fn child_hashes(&self) -> Vec<HashValue> {
+        let children = match self {
+            TrieNode::Node(_) => return Vec::new(),
+            TrieNode::Extension(extension) => std::slice::from_ref(&extension.branch),
+            TrieNode::Branch(branch) => &branch.children[..],
+        };
+        let mut hashes = Vec::new();
+        for child in children.iter() {
+            match child {
+                TrieNodeLink::HashValue(hash_value) => hashes.push(*hash_value),
+                TrieNodeLink::Inline(trie_node) => hashes.extend(trie_node.child_hashes()),
+                _ => {}
+            }
+        }
+        hashes
+    }
+
     /// 将 TridNode 压缩，压缩的过程就是将节点存入数据库中, 并返回一个 TrieNodeLink::HashValue
-    pub fn collapse(self, db: &mut impl Database) -> Result<TrieNodeLink> {
+    /// 根节点需要一个 root hash，所以总是被存入数据库中
+    pub fn collapse<H>(self, db: &mut impl Database) -> Result<TrieNodeLink>
+    where
+        H: TrieHasher,
+    {
+        let trie_node = self.collapse_children::<H>(db)?;
+        // 编码 TrieNode
+        let bin_node = trie_node.encode();
+        store::<H>(db, bin_node)
+    }
+
+    /// 压缩子节点，编码小于 hash 长度的子节点不存入数据库中，而是返回 TrieNodeLink::Inline 内嵌在父节点里
+    pub fn collapse_inline<H>(self, db: &mut impl Database) -> Result<TrieNodeLink>
+    where
+        H: TrieHasher,
+    {
+        let trie_node = self.collapse_children::<H>(db)?;
+        let bin_node = trie_node.encode();
+        if can_inline(&bin_node) {
+            return Ok(TrieNodeLink::Inline(Box::new(trie_node)));
+        }
+        store::<H>(db, bin_node)
+    }
+
+    /// 压缩 TrieNode 的子节点
+    fn collapse_children<H>(self, db: &mut impl Database) -> Result<Self>
+    where
+        H: TrieHasher,
+    {
         let trie_node = match self {
             // 如果是 TrieNode::Node, 那么直接返回
-            TrieNode::Node(_) => TrieNode::from(self),
+            TrieNode::Node(_) => self,
             // 如果是 TrieNode::Extension, 那么将其分支节点进行压缩
             TrieNode::Extension(Extension {
                 partial_key,
                 branch,
             }) => Extension {
                 partial_key,
-                branch: branch.collapse(db)?,
+                branch: branch.collapse_inline::<H>(db)?,
             }
             .into(),
             // 如果是 TrieNode::Branch, 那么将其分支节点进行压缩
@@ -80,22 +219,27 @@ impl TrieNode {
                 let mut children: [TrieNodeLink; 16] =
                     array_init::array_init(|_| TrieNodeLink::Empty);
                 for (idx, child) in old_children.into_iter().enumerate() {
-                    children[idx] = child.collapse(db)?;
+                    children[idx] = child.collapse_inline::<H>(db)?;
                 }
                 Branch { children, value }.into()
             }
         };
 
-        // 使用 bincode 序列化 TrieNode
-        let bin_node = bincode::serialize(&trie_node)?;
-        // 使用 util::hash 计算 TrieNode 的 hash 值
-        let hash_value = util::hash(&bin_node);
-        // 将 TrieNode 存入数据库中
-        db.insert(hash_value, bin_node)?;
-
-        Ok(TrieNodeLink::HashValue(hash_value))
+        Ok(trie_node)
     }
+}
 
+/// 将编码后的节点存入数据库中, 并返回一个 TrieNodeLink::HashValue
+fn store<H>(db: &mut impl Database, bin_node: Vec<u8>) -> Result<TrieNodeLink>
+where
+    H: TrieHasher,
+{
+    // 使用 H 计算 TrieNode 的 hash 值
+    let hash_value = H::hash(&bin_node);
+    // 将 TrieNode 存入数据库中
+    db.insert(hash_value, bin_node)?;
+
+    Ok(TrieNodeLink::HashValue(hash_value))
 }
 
 /// 将 Extension 转换为 TrieNode
@@ -120,10 +264,12 @@ impl From<Branch> for TrieNode {
 }
 
 /// 表现一个 TrieNode 的链接
-#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Hash)]
 pub enum TrieNodeLink {
     TrieNode(Box<TrieNode>),
     HashValue(HashValue),
+    /// 已经压缩的子节点，因为编码小于 hash 的长度，直接内嵌在父节点里，不单独存入数据库中
+    Inline(Box<TrieNode>),
     Empty,
 }
 
@@ -138,13 +284,14 @@ impl TrieNodeLink {
     /// 从 TrieNodeLink 中获得数据
     pub fn get_value(&self, db: &impl Database, key_nb: &NibbleSlice) -> Result<Option<Vec<u8>>> {
         match self {
-            TrieNodeLink::TrieNode(trie_node) => trie_node.get_value(db, key_nb),
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                trie_node.get_value(db, key_nb)
+            }
             TrieNodeLink::HashValue(hash_value) => {
-                let bin_node = db.get(hash_value)?.ok_or(TrieError::Database(format!(
+                let trie_node = db.get_node(hash_value)?.ok_or(TrieError::Database(format!(
                     "value for `{}` not found",
                     hex::encode(hash_value)
                 )))?;
-                let trie_node: TrieNode = bincode::deserialize(&bin_node)?;
                 trie_node.get_value(db, key_nb)
             }
             TrieNodeLink::Empty => Ok(None),
@@ -159,13 +306,16 @@ impl TrieNodeLink {
         key_nb: &NibbleSlice,
     ) -> Result<bool> {
         match self {
-            TrieNodeLink::TrieNode(trie_node) => trie_node.get_proof(db, proof_db, key_nb),
+            // 内嵌的节点已经包含在父节点里，不需要放入 proof_db 中
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                trie_node.get_proof(db, proof_db, key_nb)
+            }
             TrieNodeLink::HashValue(hash_value) => {
                 let bin_node = db.get(hash_value)?.ok_or(TrieError::Database(format!(
                     "value for `{}` not found",
                     hex::encode(hash_value)
                 )))?;
-                let trie_node: TrieNode = bincode::deserialize(&bin_node)?;
+                let trie_node = TrieNode::decode(&bin_node)?;
                 proof_db.insert(*hash_value, bin_node)?;
                 trie_node.get_proof(db, proof_db, key_nb)
             }
@@ -173,6 +323,44 @@ impl TrieNodeLink {
         }
     }
 
+    /// 从 TrieNodeLink 中获得前缀 proof, 路径上以及前缀对应的子树里所有的节点都会被放入 proof_db 中
+    pub fn get_prefix_proof(
+        &self,
+        db: &impl Database,
+        proof_db: &mut impl Database,
+        prefix_nb: &NibbleSlice,
+    ) -> Result<()> {
+        match self {
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                trie_node.get_prefix_proof(db, proof_db, prefix_nb)
+            }
+            TrieNodeLink::HashValue(hash_value) => {
+                let bin_node = db.get(hash_value)?.ok_or(TrieError::Database(format!(
+                    "value for `{}` not found",
+                    hex::encode(hash_value)
+                )))?;
+                let trie_node = TrieNode::decode(&bin_node)?;
+                proof_db.insert(*hash_value, bin_node)?;
+                trie_node.get_prefix_proof(db, proof_db, prefix_nb)
+            }
+            TrieNodeLink::Empty => Ok(()),
+        }
+    }
+
+    /// 展开 TrieNodeLink, 数据库中找不到的 HashValue 保持不变, 内嵌的节点也会被展开
+    pub fn expand(self, db: &impl Database) -> Result<TrieNodeLink> {
+        match self {
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                Ok(trie_node.expand(db)?.into())
+            }
+            TrieNodeLink::HashValue(hash_value) => match db.get_node(&hash_value)? {
+                Some(trie_node) => Ok(trie_node.expand(db)?.into()),
+                None => Ok(self),
+            },
+            TrieNodeLink::Empty => Ok(self),
+        }
+    }
+
     /// 向 TrieNodeLink 中插入一个键值对
     /// 注意: 值的类型是 Vec<u8>, 并且在递归传递中使用了移动语义, 没有引入额外的堆分配
     pub fn insert(
@@ -183,14 +371,18 @@ impl TrieNodeLink {
     ) -> Result<Self> {
         match self {
             // 如果是 TrieNodeLink::TrieNode, 那么直接调用 TrieNode::insert
-            TrieNodeLink::TrieNode(trie_node) => Ok(trie_node.insert(db, key_nb, value)?.into()),
+            // 内嵌的节点也一样，插入之后变为 TrieNodeLink::TrieNode，提交时重新压缩
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                Ok(trie_node.insert(db, key_nb, value)?.into())
+            }
             // 如果是 TrieNodeLink::HashValue, 那么先从数据库中读取 TrieNode, 然后调用 TrieNode::insert
             TrieNodeLink::HashValue(hash_value) => {
-                let bin_node = db.get(&hash_value)?.ok_or(TrieError::Database(format!(
-                    "Value for `{}` not found",
-                    hex::encode(hash_value)
-                )))?;
-                let trie_node: TrieNode = bincode::deserialize(&bin_node)?;
+                let trie_node = db
+                    .get_node(&hash_value)?
+                    .ok_or(TrieError::Database(format!(
+                        "Value for `{}` not found",
+                        hex::encode(hash_value)
+                    )))?;
                 Ok(trie_node.insert(db, key_nb, value)?.into())
             }
             // 如果是 TrieNodeLink::Empty, 那么直接创建一个 Node
@@ -201,27 +393,116 @@ impl TrieNodeLink {
         }
     }
 
-    /// 压缩 TrieNodeLink 
-    pub fn collapse(self, db: &mut impl Database) -> Result<TrieNodeLink> {
+    /// 从 TrieNodeLink 中删除一个 key, 返回删除后的 TrieNodeLink 和被删除的值
+    pub fn remove(
+        self,
+        db: &impl Database,
+        key_nb: &NibbleSlice,
+    ) -> Result<(TrieNodeLink, Option<Vec<u8>>)> {
+        match self {
+            // 如果是 TrieNodeLink::TrieNode 或 TrieNodeLink::Inline, 那么直接调用 TrieNode::remove
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                trie_node.remove(db, key_nb)
+            }
+            // 如果是 TrieNodeLink::HashValue, 那么先从数据库中读取 TrieNode, 然后调用 TrieNode::remove
+            TrieNodeLink::HashValue(hash_value) => {
+                let trie_node = db
+                    .get_node(&hash_value)?
+                    .ok_or(TrieError::Database(format!(
+                        "Value for `{}` not found",
+                        hex::encode(hash_value)
+                    )))?;
+                match trie_node.remove(db, key_nb)? {
+                    // 如果没有删除任何数据, 保留原来的 HashValue, 避免重新压缩
+                    (_, None) => Ok((self, None)),
+                    removed => Ok(removed),
+                }
+            }
+            // 如果是 TrieNodeLink::Empty, 没有可以删除的数据
+            TrieNodeLink::Empty => Ok((self, None)),
+        }
+    }
+
+    /// 从数据库中读取 key 的路径上的节点，将路径上的 HashValue 和内嵌节点替换为 TrieNode，
+    /// 删除之后可能与路径上的节点合并的兄弟节点也一起读取。
+    /// 替换前后 trie 的内容不变，读取失败时 trie 仍然是完整的。插入和删除之前先调用，
+    /// 之后的插入和删除不会再读取数据库，不会因为读取失败而丢掉已经取出的节点
+    pub fn load_path(&mut self, db: &impl Database, key_nb: &NibbleSlice) -> Result<()> {
+        self.load(db)?;
+        match self {
+            TrieNodeLink::TrieNode(trie_node) => trie_node.load_path(db, key_nb),
+            _ => Ok(()),
+        }
+    }
+
+    /// 将 HashValue 或内嵌节点替换为 TrieNode，读取失败时保持不变
+    fn load(&mut self, db: &impl Database) -> Result<()> {
+        match self {
+            TrieNodeLink::HashValue(hash_value) => {
+                let trie_node = db.get_node(hash_value)?.ok_or(TrieError::Database(format!(
+                    "Value for `{}` not found",
+                    hex::encode(hash_value)
+                )))?;
+                *self = trie_node.into();
+            }
+            TrieNodeLink::Inline(_) => {
+                if let TrieNodeLink::Inline(trie_node) = std::mem::take(self) {
+                    *self = TrieNodeLink::TrieNode(trie_node);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// 将 TrieNodeLink 解析为 TrieNode, 如果是 HashValue, 那么从数据库中读取
+    pub fn resolve(self, db: &impl Database) -> Result<Option<TrieNode>> {
+        match self {
+            TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node) => {
+                Ok(Some(*trie_node))
+            }
+            TrieNodeLink::HashValue(hash_value) => {
+                let trie_node = db
+                    .get_node(&hash_value)?
+                    .ok_or(TrieError::Database(format!(
+                        "Value for `{}` not found",
+                        hex::encode(hash_value)
+                    )))?;
+                Ok(Some(trie_node))
+            }
+            TrieNodeLink::Empty => Ok(None),
+        }
+    }
+
+    /// 压缩 TrieNodeLink
+    pub fn collapse<H>(self, db: &mut impl Database) -> Result<TrieNodeLink>
+    where
+        H: TrieHasher,
+    {
         match self {
             // 如果是 TrieNodeLink::TrieNode, 那么直接调用 TrieNode::collapse
-            TrieNodeLink::TrieNode(trie_node) => Ok(trie_node.collapse(db)?),
-            // 其他情况, HashValue 或 Empty, 直接返回
+            TrieNodeLink::TrieNode(trie_node) => Ok(trie_node.collapse::<H>(db)?),
+            // 其他情况, HashValue, Inline 或 Empty, 直接返回
+            _ => Ok(self),
+        }
+    }
+
+    /// 压缩子节点的 TrieNodeLink, 编码小于 hash 长度的子节点会被内嵌
+    pub fn collapse_inline<H>(self, db: &mut impl Database) -> Result<TrieNodeLink>
+    where
+        H: TrieHasher,
+    {
+        match self {
+            TrieNodeLink::TrieNode(trie_node) => Ok(trie_node.collapse_inline::<H>(db)?),
             _ => Ok(self),
         }
     }
 }
 
 /// 将 TrieNode 转换为 Vec<u8>
-impl TryFrom<TrieNode> for Vec<u8> {
-    type Error = TrieError;
-
-    fn try_from(value: TrieNode) -> std::result::Result<Self, Self::Error> {
-        match value {
-            TrieNode::Extension(extension) => Ok(extension.try_into()?),
-            TrieNode::Node(node) => Ok(node.try_into()?),
-            TrieNode::Branch(branch) => Ok(branch.try_into()?),
-        }
+impl From<TrieNode> for Vec<u8> {
+    fn from(value: TrieNode) -> Self {
+        value.encode()
     }
 }
 
@@ -1,12 +1,11 @@
 use crate::database::Database;
 use crate::trie::node::{Branch, Extension, TrieNode, TrieNodeLink};
 use crate::trie::util;
+use crate::NibbleVec;
 use crate::{NibbleSlice, Result};
-use crate::{NibbleVec, TrieError};
-use serde::{Deserialize, Serialize};
 
 /// 叶子节点
-#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Hash)]
 pub struct Node {
     pub rest_of_key: NibbleVec,
     pub value: Vec<u8>,
@@ -35,7 +34,7 @@ impl Node {
 
         // 获得叶子节点的key与要插入的key的公共前缀
         let (shared, rest_of_key, rest_of_key_nb) =
-            util::parse_nibble_slices_shared_portion(&self.rest_of_key, &key_nb);
+            util::parse_nibble_slices_shared_portion(&self.rest_of_key, key_nb);
         // 构建一个新的 Branch
         let branch = Branch::new();
 
@@ -45,8 +44,8 @@ impl Node {
         let branch = branch.insert(db, rest_of_key_nb, value)?;
 
         // 如果 shared 为空，则直接返回 branch
-        Ok(if shared.len() == 0 {
-            branch.into()
+        Ok(if shared.is_empty() {
+            branch
         } else {
             // 否则将 shared 作为 partial_key, 新的 branch 作为 branch, 构建一个新的 Extension
             Extension {
@@ -67,13 +66,22 @@ impl Node {
         // 否则返回 None
         Ok(None)
     }
+
+    /// 从叶子节点中删除数据, 返回删除后的 TrieNodeLink 和被删除的值
+    pub fn remove(self, key_nb: &NibbleSlice) -> Result<(TrieNodeLink, Option<Vec<u8>>)> {
+        // 如果叶子节点的key与要删除的key相同，则叶子节点被删除，变为 Empty
+        if self.rest_of_key == *key_nb {
+            return Ok((TrieNodeLink::Empty, Some(self.value)));
+        }
+
+        // 否则叶子节点保持不变
+        Ok((self.into(), None))
+    }
 }
 
 /// 将 Node 转换为 Vec<u8>
-impl TryFrom<Node> for Vec<u8> {
-    type Error = TrieError;
-
-    fn try_from(value: Node) -> std::result::Result<Self, Self::Error> {
-        Ok(bincode::serialize(&value)?)
+impl From<Node> for Vec<u8> {
+    fn from(value: Node) -> Self {
+        TrieNode::from(value).encode()
     }
 }
@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+
+use super::TrieHasher;
+use crate::database::Database;
+use crate::{HashValue, Result, TrieError};
+
+/// 紧凑格式的 proof
+///
+/// 与 MemoryDatabase 不同，Proof 按照节点被收集的顺序（从根节点到叶子节点）保存节点的编码，
+/// 不保存节点的 hash，验证方可以重新计算每个节点的 hash。
+///
+/// 同一个节点只会保存一次，所以多个 key 的路径可以共享同一个 Proof。
+///
+/// 编码格式: 依次写入每个节点，每个节点由 4 字节大端序的长度和节点数据组成
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Proof {
+    nodes: Vec<Vec<u8>>,
+    // 节点 hash 到 nodes 索引的映射，不参与编码，解码时重新计算
+    index: HashMap<HashValue, usize>,
+}
+
+impl Proof {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            index: HashMap::new(),
+        }
+    }
+
+    /// 获得 proof 里所有节点的编码
+    pub fn nodes(&self) -> &[Vec<u8>] {
+        &self.nodes
+    }
+
+    /// 将 proof 编码为 Vec<u8>
+    pub fn encode(&self) -> Vec<u8> {
+        let len = self.nodes.iter().map(|node| 4 + node.len()).sum();
+        let mut buf = Vec::with_capacity(len);
+        for node in self.nodes.iter() {
+            buf.extend_from_slice(&(node.len() as u32).to_be_bytes());
+            buf.extend_from_slice(node);
+        }
+        buf
+    }
+
+    /// 从 &[u8] 解码出 proof, 数据格式不正确时返回 TrieError::InvalidProof
+    /// 节点的 hash 使用 H 重新计算，H 必须与生成 proof 的 trie 使用的 hash 函数相同
+    pub fn decode<H>(mut buf: &[u8]) -> Result<Self>
+    where
+        H: TrieHasher,
+    {
+        let mut proof = Self::new();
+        while !buf.is_empty() {
+            // 读取节点的长度
+            if buf.len() < 4 {
+                return Err(TrieError::InvalidProof);
+            }
+            let (len, rest) = buf.split_at(4);
+            let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            // 读取节点的数据
+            if rest.len() < len {
+                return Err(TrieError::InvalidProof);
+            }
+            let (node, rest) = rest.split_at(len);
+            // 重新计算节点的 hash
+            proof.insert(H::hash(node), node.to_vec())?;
+            buf = rest;
+        }
+        Ok(proof)
+    }
+}
+
+/// 实现 Database trait, 这样收集 proof 的代码可以直接把节点写入 Proof,
+/// 验证 proof 的代码也可以直接从 Proof 中读取节点
+impl Database for Proof {
+    fn get(&self, key: &HashValue) -> Result<Option<Vec<u8>>> {
+        Ok(self.index.get(key).map(|idx| self.nodes[*idx].clone()))
+    }
+
+    fn insert(&mut self, key: HashValue, value: Vec<u8>) -> Result<()> {
+        // 同一个节点只保存一次
+        if !self.index.contains_key(&key) {
+            self.index.insert(key, self.nodes.len());
+            self.nodes.push(value);
+        }
+        Ok(())
+    }
+
+    fn exists(&self, key: &HashValue) -> Result<bool> {
+        Ok(self.index.contains_key(key))
+    }
+
+    fn remove(&mut self, key: &HashValue) -> Result<()> {
+        // 删除节点后，后面节点的索引都会改变，需要重新建立索引
+        if let Some(idx) = self.index.remove(key) {
+            self.nodes.remove(idx);
+            for node_idx in self.index.values_mut() {
+                if *node_idx > idx {
+                    *node_idx -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<HashValue>> {
+        Ok(self.index.keys().copied().collect())
+    }
+}
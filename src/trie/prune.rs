@@ -0,0 +1,163 @@
+use super::node::TrieNode;
+use super::{load_latest_root, util, version};
+use crate::database::{Database, MemoryDatabase, PINNED_ROOTS_KEY};
+use crate::{HashValue, Result, TrieError};
+
+// 节点的引用计数 = 引用它的节点的数量 + 它作为根节点被固定的次数
+// 引用计数保存在由节点 hash 计算出来的 key 下，引用计数归零时节点会被删除
+
+/// 节点引用计数的 key
+pub(crate) fn refcount_key(hash_value: &HashValue) -> HashValue {
+    util::hash(&[b"refcount".as_slice(), hash_value].concat())
+}
+
+/// 获得节点的引用计数, 没有记录时为 0
+fn get_refcount(db: &impl Database, hash_value: &HashValue) -> Result<u64> {
+    match db.get(&refcount_key(hash_value))? {
+        Some(bin_count) => Ok(bincode::deserialize(&bin_count)?),
+        None => Ok(0),
+    }
+}
+
+/// 设置节点的引用计数, 为 0 时删除记录
+pub(crate) fn set_refcount(
+    db: &mut impl Database,
+    hash_value: &HashValue,
+    count: u64,
+) -> Result<()> {
+    if count == 0 {
+        db.remove(&refcount_key(hash_value))
+    } else {
+        db.insert(refcount_key(hash_value), bincode::serialize(&count)?)
+    }
+}
+
+/// 增加节点的引用计数
+fn retain(db: &mut impl Database, hash_value: &HashValue) -> Result<()> {
+    let count = get_refcount(db, hash_value)?;
+    set_refcount(db, hash_value, count + 1)
+}
+
+/// 减少节点的引用计数，引用计数归零的节点会被删除，并继续减少它的子节点的引用计数
+/// 返回删除的节点数量
+fn release(db: &mut impl Database, hash_value: &HashValue) -> Result<usize> {
+    let mut removed = 0;
+    let mut stack = vec![*hash_value];
+    while let Some(hash_value) = stack.pop() {
+        match get_refcount(db, &hash_value)? {
+            // 没有引用计数的节点不是由 commit 写入的，不删除
+            0 => continue,
+            1 => {
+                if let Some(bin_node) = db.get(&hash_value)? {
+                    let trie_node = TrieNode::decode(&bin_node)?;
+                    stack.extend(trie_node.child_hashes());
+                    db.remove(&hash_value)?;
+                    removed += 1;
+                }
+                set_refcount(db, &hash_value, 0)?;
+            }
+            count => set_refcount(db, &hash_value, count - 1)?,
+        }
+    }
+    Ok(removed)
+}
+
+/// 将 commit 压缩出来的节点写入数据库
+/// 只有数据库里还没有的节点才会被写入，并增加它们引用的子节点的引用计数
+pub(crate) fn write_nodes(db: &mut impl Database, staging: &MemoryDatabase) -> Result<()> {
+    // 先找出所有的新节点，再增加引用计数，避免子节点因为已经被新节点引用而被误认为是旧节点
+    let mut new_nodes = Vec::new();
+    for (hash_value, bin_node) in staging {
+        if get_refcount(db, hash_value)? == 0 {
+            new_nodes.push((hash_value, bin_node));
+        }
+    }
+    for (hash_value, bin_node) in new_nodes {
+        let trie_node = TrieNode::decode(bin_node)?;
+        for child_hash in trie_node.child_hashes() {
+            retain(db, &child_hash)?;
+        }
+        db.insert(*hash_value, bin_node.clone())?;
+    }
+    Ok(())
+}
+
+/// 获得所有被固定的 root hash, 按固定的顺序排列
+pub(crate) fn load_pinned_roots(db: &impl Database) -> Result<Vec<HashValue>> {
+    match db.get(&PINNED_ROOTS_KEY)? {
+        Some(bin_roots) => Ok(bincode::deserialize(&bin_roots)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// 保存所有被固定的 root hash
+pub(crate) fn save_pinned_roots(db: &mut impl Database, roots: &[HashValue]) -> Result<()> {
+    db.insert(PINNED_ROOTS_KEY, bincode::serialize(roots)?)
+}
+
+/// 固定 root hash，固定之后它引用的节点不会被删除
+/// 与最近一次固定的 root hash 相同时不再重复固定，重复提交同一个 root hash 不会让固定记录越来越多
+pub(crate) fn pin(db: &mut impl Database, root_hash: &HashValue) -> Result<()> {
+    let mut roots = load_pinned_roots(db)?;
+    if roots.last() == Some(root_hash) {
+        return Ok(());
+    }
+    roots.push(*root_hash);
+    save_pinned_roots(db, &roots)?;
+    retain(db, root_hash)
+}
+
+/// 取消固定 root hash, 删除不再被引用的节点，返回删除的节点数量
+/// 同一个 root hash 被固定了多次时，只取消最早的一次
+/// 最近一次提交的 root hash 只剩一次固定时不能取消，返回 TrieError::LatestRoot
+pub(crate) fn unpin(db: &mut impl Database, root_hash: &HashValue) -> Result<usize> {
+    let mut roots = load_pinned_roots(db)?;
+    let idx = roots
+        .iter()
+        .position(|root| root == root_hash)
+        .ok_or(TrieError::InvalidHashValue)?;
+    if load_latest_root(db)? == Some(*root_hash)
+        && roots.iter().filter(|root| *root == root_hash).count() == 1
+    {
+        return Err(TrieError::LatestRoot);
+    }
+    roots.remove(idx);
+    save_pinned_roots(db, &roots)?;
+    release(db, root_hash)
+}
+
+/// 只保留最近 keep_last 个不同的 root hash，取消固定其余的 root hash，返回删除的节点数量
+/// 最近一次提交的 root hash 总是被保留
+pub(crate) fn unpin_old_roots(db: &mut impl Database, keep_last: usize) -> Result<usize> {
+    let roots = load_pinned_roots(db)?;
+    let mut kept: Vec<HashValue> = load_latest_root(db)?.into_iter().collect();
+    let mut distinct = Vec::new();
+    for root_hash in roots.iter().rev() {
+        if distinct.len() == keep_last {
+            break;
+        }
+        if !distinct.contains(root_hash) {
+            distinct.push(*root_hash);
+        }
+    }
+    kept.extend(distinct);
+
+    let mut removed = 0;
+    for root_hash in roots.iter().filter(|root| !kept.contains(root)) {
+        removed += unpin(db, root_hash)?;
+    }
+    Ok(removed)
+}
+
+/// 删除 root hash 已经被删除的版本记录
+pub(crate) fn prune_versions(db: &mut impl Database) -> Result<()> {
+    let mut removed = Vec::new();
+    for version in version::load_versions(db)? {
+        if let Some(root_hash) = version.root_hash {
+            if get_refcount(db, &root_hash)? == 0 {
+                removed.push(version.number);
+            }
+        }
+    }
+    version::remove_versions(db, &removed)
+}
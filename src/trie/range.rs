@@ -0,0 +1,167 @@
+use std::cmp::Ordering;
+
+use serde::de::DeserializeOwned;
+
+use super::node::{Branch, Extension, TrieNode, TrieNodeLink};
+use super::{collect_proof, util, Proof, TrieIterator};
+use crate::database::Database;
+use crate::{HashValue, NibbleSlice, Result, TrieError};
+
+/// 范围 proof，证明了 [start, last_key] 范围内的全部数据
+///
+/// proof 里只包含 start 和 last_key 两条边界路径上的节点，范围内的节点由验证方
+/// 根据 entries 重新构建，所以 proof 的大小与范围内数据的多少无关。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RangeProof<V> {
+    /// 范围内的 key-value，按 key 的字典序排列
+    pub entries: Vec<(Vec<u8>, V)>,
+    /// proof 实际证明的范围的右边界
+    /// 受 limit 限制时为最后一条数据的 key，否则为请求的 end
+    pub last_key: Vec<u8>,
+    /// 边界路径上的节点
+    pub proof: Proof,
+}
+
+/// 从 db 里获得 root hash 对应的 trie 的范围 proof，参数与 Trie::get_range_proof 相同
+pub(crate) fn get_range_proof<V>(
+    db: &impl Database,
+    root_hash: &HashValue,
+    start: &[u8],
+    end: &[u8],
+    limit: usize,
+) -> Result<RangeProof<V>>
+where
+    V: DeserializeOwned,
+{
+    if start > end {
+        return Err(TrieError::InvalidKey);
+    }
+    if db.get(root_hash)?.is_none() {
+        return Err(TrieError::UnknownRoot);
+    }
+    let limit = limit.max(1);
+    // 从 start 开始遍历，多取一条数据用来判断范围内的数据是否超过了 limit
+    let start_nb = util::convert_bytes_to_nibbles(start);
+    let iter = TrieIterator::with_start(db, TrieNodeLink::HashValue(*root_hash), &start_nb)?;
+    let mut entries = Vec::new();
+    for item in iter {
+        let (key, value) = item?;
+        if key.as_slice() > end || entries.len() > limit {
+            break;
+        }
+        entries.push((key, value));
+    }
+    // 超过了 limit，proof 的右边界为最后一条数据的 key
+    let last_key = if entries.len() > limit {
+        entries.truncate(limit);
+        entries[limit - 1].0.clone()
+    } else {
+        end.to_vec()
+    };
+    // 将左右边界路径上的节点收集到 proof 里
+    let mut proof = Proof::new();
+    collect_proof(db, &mut proof, root_hash, start)?;
+    collect_proof(db, &mut proof, root_hash, &last_key)?;
+    Ok(RangeProof {
+        entries,
+        last_key,
+        proof,
+    })
+}
+
+/// 以某个 nibble 路径为前缀的子树与范围的关系
+enum Span {
+    /// 子树里的 key 都在范围外
+    Outside,
+    /// 子树里的 key 都在范围内
+    Inside,
+    /// 子树跨越了范围的边界，路径是 start 或 last 的前缀
+    Partial,
+}
+
+/// 判断以 path 为前缀的 key 与 [start_nb, last_nb] 的关系
+fn classify(path: &NibbleSlice, start_nb: &NibbleSlice, last_nb: &NibbleSlice) -> Span {
+    // 比较 path 与边界的共同长度部分
+    let cmp_prefix = |bound: &NibbleSlice| {
+        let len = path.len().min(bound.len());
+        path[..len].cmp(&bound[..len])
+    };
+    let cmp_start = cmp_prefix(start_nb);
+    let cmp_last = cmp_prefix(last_nb);
+
+    // 所有 key 都小于 start，或者都大于 last
+    let below_start = cmp_start == Ordering::Less;
+    let above_last = cmp_last == Ordering::Greater
+        || (cmp_last == Ordering::Equal && path.len() > last_nb.len());
+    if below_start || above_last {
+        return Span::Outside;
+    }
+
+    // 所有 key 都大于等于 start，并且都小于等于 last
+    let at_least_start = cmp_start == Ordering::Greater
+        || (cmp_start == Ordering::Equal && path.len() >= start_nb.len());
+    let at_most_last = cmp_last == Ordering::Less;
+    if at_least_start && at_most_last {
+        return Span::Inside;
+    }
+
+    Span::Partial
+}
+
+/// 删除部分 trie 里所有 key 在 [start_nb, last_nb] 范围内的数据，范围外的节点保持不变
+///
+/// 跨越边界的节点一定在 start 或 last 的路径上，如果它们不在部分 trie 里，
+/// 说明 proof 缺少了边界路径上的节点，返回 TrieError::InvalidProof
+pub(crate) fn prune(
+    trie_node_link: TrieNodeLink,
+    path: &NibbleSlice,
+    start_nb: &NibbleSlice,
+    last_nb: &NibbleSlice,
+) -> Result<TrieNodeLink> {
+    let in_range = |key: &NibbleSlice| start_nb <= key && key <= last_nb;
+
+    let trie_node = match (classify(path, start_nb, last_nb), trie_node_link) {
+        (_, TrieNodeLink::Empty) => return Ok(TrieNodeLink::Empty),
+        (Span::Outside, trie_node_link) => return Ok(trie_node_link),
+        (Span::Inside, _) => return Ok(TrieNodeLink::Empty),
+        (Span::Partial, TrieNodeLink::TrieNode(trie_node) | TrieNodeLink::Inline(trie_node)) => {
+            *trie_node
+        }
+        (Span::Partial, TrieNodeLink::HashValue(_)) => return Err(TrieError::InvalidProof),
+    };
+
+    let trie_node_link = match trie_node {
+        // 叶子节点只有一个 key，直接判断是否在范围内
+        TrieNode::Node(node) => {
+            if in_range(&[path, &node.rest_of_key].concat()) {
+                TrieNodeLink::Empty
+            } else {
+                node.into()
+            }
+        }
+        // 扩展节点下的 key 都以 path + partial_key 开头，由 branch 来处理
+        TrieNode::Extension(Extension {
+            partial_key,
+            branch,
+        }) => match prune(branch, &[path, &partial_key].concat(), start_nb, last_nb)? {
+            TrieNodeLink::Empty => TrieNodeLink::Empty,
+            branch => Extension {
+                partial_key,
+                branch,
+            }
+            .into(),
+        },
+        // 分支节点，依次处理每一个 child 和分支节点的 value
+        TrieNode::Branch(Branch { children, value }) => {
+            let mut branch = Branch::new();
+            for (idx, child) in children.into_iter().enumerate() {
+                let child = prune(child, &[path, &[idx as u8]].concat(), start_nb, last_nb)?;
+                branch.set_child(idx, child);
+            }
+            branch.value = value.filter(|_| !in_range(path));
+            branch.into()
+        }
+    };
+
+    Ok(trie_node_link)
+}
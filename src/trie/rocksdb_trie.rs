@@ -1,42 +1,93 @@
 use serde::{de::DeserializeOwned, Serialize};
 use std::{marker::PhantomData, path::PathBuf};
 
-use super::{node::TrieNodeLink, Trie};
-use crate::database::RocksdbDatabase;
+use super::{load_latest_root, node::TrieNodeLink, Blake2bHasher, Trie, TrieHasher};
+use crate::database::{Database, RocksdbDatabase};
+use crate::{HashValue, Result, TrieError};
 
 /// Rocksdb Trie
-pub struct RocksdbTrie<K, V> {
+///
+/// H 是计算节点 hash 使用的 hash 函数，默认为 Blake2bHasher
+/// D 是保存节点的数据库，默认为 RocksdbDatabase，也可以使用 CachingDatabase 等包装过的数据库
+pub struct RocksdbTrie<K, V, H = Blake2bHasher, D = RocksdbDatabase> {
     root_node: TrieNodeLink,
-    db: RocksdbDatabase,
+    db: D,
     dirty: bool,
-    // K, V 是 Trie trait 的方法里使用的, RocksdbTrie 里没有使用
+    // K, V, H 是 Trie trait 的方法里使用的, RocksdbTrie 里没有使用
     // 使用 PhantomData 来避免编译器报错
     _k: PhantomData<K>,
     _v: PhantomData<V>,
+    _h: PhantomData<H>,
 }
 
-impl<K, V> RocksdbTrie<K, V> {
+impl<K, V, H> RocksdbTrie<K, V, H> {
     pub fn new(db_path: PathBuf) -> Self {
+        Self::with_database(RocksdbDatabase::new(db_path))
+    }
+
+    /// 打开数据库，从最近一次提交的 root hash 继续
+    /// 如果数据库里没有提交过数据，那么从空的 trie 开始
+    pub fn open(db_path: PathBuf) -> Result<Self> {
+        Self::open_database(RocksdbDatabase::new(db_path))
+    }
+
+    /// 打开数据库，从指定的 root hash 开始，不需要再手动调用 revert
+    /// 如果数据库里没有这个 root hash 对应的节点，那么返回 TrieError::UnknownRoot
+    pub fn open_at(db_path: PathBuf, root_hash: HashValue) -> Result<Self> {
+        Self::open_database_at(RocksdbDatabase::new(db_path), root_hash)
+    }
+}
+
+impl<K, V, H, D> RocksdbTrie<K, V, H, D>
+where
+    D: Database,
+{
+    /// 使用指定的数据库创建一个空的 trie
+    /// 比如 `CachingDatabase::new(RocksdbDatabase::new(db_path), capacity)`，读取节点时使用 LRU 缓存
+    pub fn with_database(db: D) -> Self {
         Self {
             root_node: TrieNodeLink::Empty,
-            db: RocksdbDatabase::new(db_path),
+            db,
             dirty: false,
             _k: PhantomData,
             _v: PhantomData,
+            _h: PhantomData,
+        }
+    }
+
+    /// 与 open 相同，使用指定的数据库
+    pub fn open_database(db: D) -> Result<Self> {
+        let mut trie = Self::with_database(db);
+        if let Some(root_hash) = load_latest_root(&trie.db)? {
+            trie.root_node = TrieNodeLink::HashValue(root_hash);
+        }
+        Ok(trie)
+    }
+
+    /// 与 open_at 相同，使用指定的数据库
+    pub fn open_database_at(db: D, root_hash: HashValue) -> Result<Self> {
+        let mut trie = Self::with_database(db);
+        if trie.db.get(&root_hash)?.is_none() {
+            return Err(TrieError::UnknownRoot);
         }
+        trie.root_node = TrieNodeLink::HashValue(root_hash);
+        Ok(trie)
     }
 }
 
 // 通常只需要在实现时才约束泛型，定义结构体的时候不需要
 // 这样保持结构体的灵活性，同时我们也可以针对不同的约束给出不同的实现
-// 当然此处为了实现 Trie trait，我们必须要约束 K, V, 
+// 当然此处为了实现 Trie trait，我们必须要约束 K, V,
 // 所以这里的约束是必须的
-impl<K, V> Trie<K, V> for RocksdbTrie<K, V>
+impl<K, V, H, D> Trie<K, V> for RocksdbTrie<K, V, H, D>
 where
     K: AsRef<[u8]>,
     V: Serialize + DeserializeOwned,
+    H: TrieHasher,
+    D: Database,
 {
-    type Database = RocksdbDatabase;
+    type Database = D;
+    type Hasher = H;
 
     fn dirty(&self) -> bool {
         self.dirty
@@ -0,0 +1,134 @@
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::node::TrieNodeLink;
+use super::{
+    collect_multi_proof, collect_prefix_proof, collect_proof, range, util, Proof, RangeProof,
+    TrieIterator,
+};
+use crate::database::{Database, MemoryDatabase};
+use crate::{HashValue, NibbleVec, Result, TrieError};
+
+/// Trie 的只读快照，绑定到一个已经提交的 root hash
+///
+/// 快照的所有方法都只需要 &self，克隆快照只会增加数据库的引用计数，
+/// 所以可以在多个线程或者协程里同时读取数据、生成 proof，同时 trie 仍然可以继续写入和提交。
+/// 数据库是 Send + Sync 时，快照也是 Send + Sync。
+///
+/// 快照不会固定它的 root hash，如果 root hash 被 prune 或者 unpin 删除了，之后将无法再读取它。
+///
+/// 空的快照没有 root hash，get_value 和遍历返回空的结果，
+/// 但是没有 root hash 的 proof 无法被验证，所以获得 proof 的方法都返回 TrieError::UnknownRoot。
+pub struct TrieSnapshot<D, K, V> {
+    db: Arc<D>,
+    root_hash: Option<HashValue>,
+    // K, V 只在方法里使用，使用 fn() -> (K, V) 使得快照是否 Send + Sync 只取决于 D
+    _kv: PhantomData<fn() -> (K, V)>,
+}
+
+impl<D, K, V> TrieSnapshot<D, K, V> {
+    /// 创建一个快照，root_hash 为 None 时表示空的 trie
+    pub fn new(db: Arc<D>, root_hash: Option<HashValue>) -> Self {
+        Self {
+            db,
+            root_hash,
+            _kv: PhantomData,
+        }
+    }
+
+    /// 获得快照绑定的 root hash
+    pub fn root_hash(&self) -> Option<HashValue> {
+        self.root_hash
+    }
+
+    /// 获得共享同一个数据库，但是绑定到另一个 root hash 的快照
+    pub fn at(&self, root_hash: HashValue) -> Self {
+        Self::new(self.db.clone(), Some(root_hash))
+    }
+
+    /// 获得快照的数据库
+    pub fn db(&self) -> &D {
+        &self.db
+    }
+
+    fn root_node(&self) -> TrieNodeLink {
+        match self.root_hash {
+            Some(root_hash) => TrieNodeLink::HashValue(root_hash),
+            None => TrieNodeLink::Empty,
+        }
+    }
+
+    /// 获得生成 proof 使用的 root hash，空的快照返回 TrieError::UnknownRoot
+    fn proof_root(&self) -> Result<HashValue> {
+        self.root_hash.ok_or(TrieError::UnknownRoot)
+    }
+}
+
+// 手动实现 Clone，derive 会要求 D, K, V 都实现 Clone
+impl<D, K, V> Clone for TrieSnapshot<D, K, V> {
+    fn clone(&self) -> Self {
+        Self::new(self.db.clone(), self.root_hash)
+    }
+}
+
+impl<D, K, V> TrieSnapshot<D, K, V>
+where
+    D: Database,
+    K: AsRef<[u8]>,
+    V: Serialize + DeserializeOwned,
+{
+    /// 获得快照里的一个 key-value
+    pub fn get_value(&self, key: &K) -> Result<Option<V>> {
+        // 将 key 转换为 nibble 形式
+        let key_nb: NibbleVec = util::convert_bytes_to_nibbles(key.as_ref());
+        match self.root_node().get_value(self.db(), &key_nb)? {
+            Some(bin_value) => Ok(Some(bincode::deserialize(&bin_value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 按 key 的字典序遍历快照里所有的 key-value
+    pub fn iter(&self) -> TrieIterator<'_, D, V> {
+        TrieIterator::new(self.db(), self.root_node())
+    }
+
+    /// 按 key 的字典序遍历快照里所有以 prefix 开头的 key-value
+    pub fn iter_prefix(&self, prefix: &[u8]) -> Result<TrieIterator<'_, D, V>> {
+        // 将 prefix 转换为 nibble 形式
+        let prefix_nb = util::convert_bytes_to_nibbles(prefix);
+        TrieIterator::with_prefix(self.db(), self.root_node(), &prefix_nb)
+    }
+
+    /// 获得 proof，返回值与 Trie::get_proof 相同
+    pub fn get_proof(&self, key: &K) -> Result<(bool, MemoryDatabase)> {
+        let root_hash = self.proof_root()?;
+        let mut proof_db = MemoryDatabase::new();
+        let exists = collect_proof(self.db(), &mut proof_db, &root_hash, key.as_ref())?;
+        Ok((exists, proof_db))
+    }
+
+    /// 获得紧凑格式的 proof，返回值与 Trie::get_compact_proof 相同
+    pub fn get_compact_proof(&self, key: &K) -> Result<(bool, Proof)> {
+        let root_hash = self.proof_root()?;
+        let mut proof = Proof::new();
+        let exists = collect_proof(self.db(), &mut proof, &root_hash, key.as_ref())?;
+        Ok((exists, proof))
+    }
+
+    /// 获得多个 key 共享的 proof，返回值与 Trie::get_multi_proof 相同
+    pub fn get_multi_proof(&self, keys: &[K]) -> Result<Proof> {
+        collect_multi_proof(self.db(), &self.proof_root()?, keys)
+    }
+
+    /// 获得前缀 proof，返回值与 Trie::get_prefix_proof 相同
+    pub fn get_prefix_proof(&self, prefix: &[u8]) -> Result<MemoryDatabase> {
+        collect_prefix_proof(self.db(), &self.proof_root()?, prefix)
+    }
+
+    /// 获得范围 proof，参数和返回值与 Trie::get_range_proof 相同
+    pub fn get_range_proof(&self, start: &[u8], end: &[u8], limit: usize) -> Result<RangeProof<V>> {
+        range::get_range_proof(self.db(), &self.proof_root()?, start, end, limit)
+    }
+}
@@ -15,6 +15,15 @@ pub fn convert_bytes_to_nibbles(bytes: &[u8]) -> NibbleVec {
     nibbles
 }
 
+/// 将 NibbleSlice 转换为 Vec<u8>, 是 convert_bytes_to_nibbles 的逆操作
+pub fn convert_nibbles_to_bytes(nibbles: &NibbleSlice) -> Vec<u8> {
+    nibbles
+        .chunks(2)
+        // 高 4 位在前，低 4 位在后
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
 /// 获得两个 NibbleSlice 的共同前缀， 并返回(共同前缀, n1去掉共同前缀的剩余部分, n2去掉共同前缀的剩余部分)
 pub fn parse_nibble_slices_shared_portion<'a, 'b>(
     n1: &'a NibbleSlice,
@@ -39,7 +48,7 @@ pub fn parse_nibble_slices_shared_portion<'a, 'b>(
         }
     }
 
-    return split(min);
+    split(min)
 }
 
 /// 获得 &[u8] 的哈希
@@ -64,13 +73,22 @@ mod util_test {
         let n2 = &[0x01, 0x02, 0x03, 0x04][..];
 
         let expeced_shared = &[0x01, 0x02, 0x03];
-        let expeced_r1 = &[];
+        let expeced_r1: &[u8] = &[];
         let expeced_r2 = &[0x04];
 
-        let (shared, r1, r2) = parse_nibble_slices_shared_portion(&n1, &n2);
+        let (shared, r1, r2) = parse_nibble_slices_shared_portion(n1, n2);
 
         assert_eq!(shared, expeced_shared);
         assert_eq!(r1, expeced_r1);
         assert_eq!(r2, expeced_r2);
     }
+
+    #[test]
+    fn convert_nibbles_to_bytes_works() {
+        let bytes = b"pellet01_state01";
+        let nibbles = convert_bytes_to_nibbles(bytes);
+
+        assert_eq!(nibbles[..2], [0x07, 0x00]);
+        assert_eq!(convert_nibbles_to_bytes(&nibbles), bytes);
+    }
 }
@@ -0,0 +1,107 @@
+use serde::{Deserialize, Serialize};
+
+use super::util;
+use crate::database::{Database, VERSIONS_KEY};
+use crate::{HashValue, Result};
+
+// 每个版本记录保存在由版本号计算出来的 key 下，提交新版本时只写入一条记录
+// VERSIONS_KEY 下只保存按从小到大排列的版本号，作为遍历版本记录的索引
+
+/// 版本记录，将版本号和标签映射到提交后的 root hash
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Version {
+    /// 版本号，比如区块高度，必须单调递增
+    pub number: u64,
+    /// 版本的标签，比如区块 hash
+    pub label: Option<Vec<u8>>,
+    /// 版本对应的 root hash，提交时 trie 为空则为 None
+    pub root_hash: Option<HashValue>,
+}
+
+/// 版本记录的 key
+pub(crate) fn version_key(number: u64) -> HashValue {
+    util::hash(&[b"version".as_slice(), &number.to_be_bytes()].concat())
+}
+
+/// 从数据库里读取所有的版本号，按从小到大排列
+pub(crate) fn load_version_numbers(db: &impl Database) -> Result<Vec<u64>> {
+    match db.get(&VERSIONS_KEY)? {
+        Some(bin_numbers) => Ok(bincode::deserialize(&bin_numbers)?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// 从数据库里读取 number 版本的记录
+pub(crate) fn load_version(db: &impl Database, number: u64) -> Result<Option<Version>> {
+    match db.get(&version_key(number))? {
+        Some(bin_version) => Ok(Some(bincode::deserialize(&bin_version)?)),
+        None => Ok(None),
+    }
+}
+
+/// 从数据库里读取所有的版本记录，按版本号从小到大排列
+pub(crate) fn load_versions(db: &impl Database) -> Result<Vec<Version>> {
+    let mut versions = Vec::new();
+    for number in load_version_numbers(db)? {
+        if let Some(version) = load_version(db, number)? {
+            versions.push(version);
+        }
+    }
+    Ok(versions)
+}
+
+/// 保存一个新的版本记录，版本号必须大于已有的所有版本号
+pub(crate) fn add_version(db: &mut impl Database, version: &Version) -> Result<()> {
+    let mut numbers = load_version_numbers(db)?;
+    numbers.push(version.number);
+    db.insert(version_key(version.number), bincode::serialize(version)?)?;
+    db.insert(VERSIONS_KEY, bincode::serialize(&numbers)?)
+}
+
+/// 删除指定版本号的版本记录
+pub(crate) fn remove_versions(db: &mut impl Database, removed: &[u64]) -> Result<()> {
+    if removed.is_empty() {
+        return Ok(());
+    }
+    for number in removed {
+        db.remove(&version_key(*number))?;
+    }
+    let numbers: Vec<_> = load_version_numbers(db)?
+        .into_iter()
+        .filter(|number| !removed.contains(number))
+        .collect();
+    db.insert(VERSIONS_KEY, bincode::serialize(&numbers)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryDatabase;
+
+    #[test]
+    fn version_records_works() {
+        let mut db = MemoryDatabase::new();
+        for number in [1, 3, 5] {
+            let version = Version {
+                number,
+                label: None,
+                root_hash: Some([number as u8; 32]),
+            };
+            add_version(&mut db, &version).unwrap();
+            // 每个版本记录保存在自己的 key 下
+            assert_eq!(load_version(&db, number).unwrap(), Some(version));
+        }
+        assert_eq!(load_version_numbers(&db).unwrap(), vec![1, 3, 5]);
+        assert!(load_version(&db, 2).unwrap().is_none());
+
+        // 删除版本记录时，索引里的版本号也一起删除
+        remove_versions(&mut db, &[3]).unwrap();
+        assert!(!db.exists(&version_key(3)).unwrap());
+        let numbers: Vec<_> = load_versions(&db)
+            .unwrap()
+            .iter()
+            .map(|version| version.number)
+            .collect();
+        assert_eq!(numbers, vec![1, 5]);
+    }
+}